@@ -1,24 +1,86 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 use anchor_spl::token::{self, TokenAccount, Token, Transfer};
 use anchor_lang::solana_program::clock::Clock;
+use anchor_lang::system_program;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::keccak;
 
 declare_id!("tHCStAk1ng1111111111111111111111111111111");
 
+// Lock tiers, ordered by minimum lock period
+pub const TIER_COUNT: usize = 4;
+pub const APY_TIERS: [ApyTier; TIER_COUNT] = [
+    ApyTier { period_days: 30, apy_bps: 500, no_early_exit: false, weight_multiplier: 10000 },   // 5.00% default, 1x
+    ApyTier { period_days: 90, apy_bps: 800, no_early_exit: false, weight_multiplier: 12500 },   // 8.00%, 1.25x
+    ApyTier { period_days: 180, apy_bps: 1200, no_early_exit: false, weight_multiplier: 15000 }, // 12.00%, 1.5x
+    ApyTier { period_days: 365, apy_bps: 1500, no_early_exit: true, weight_multiplier: 20000 },  // 15.00%, no early exit, 2x
+];
+
+// Current StakingAuthority layout version. Accounts written before the version field existed read as
+// version 0 once grown, since the field is zero-filled.
+// Bump whenever fields are appended so migrate_authority can grow existing accounts.
+pub const AUTHORITY_VERSION: u8 = 24;
+
+// Reward formula positions accrue under, stamped on open and by batch_migrate_positions.
+// Version 0 is the original program's formula: deposit * apy over the time since the last claim.
+pub const REWARD_FORMULA_VERSION: u8 = 1;
+
+// Most positions batch_migrate_positions will process in one transaction
+pub const MAX_MIGRATION_BATCH: usize = 10;
+
+// Payouts kept in each position's claim history ring buffer
+pub const CLAIM_HISTORY_LEN: usize = 16;
+
+// Fixed-point scale for the reward index
+pub const REWARD_INDEX_PRECISION: u128 = 1_000_000_000_000;
+
+// Seconds in a reward year
+pub const SECONDS_PER_YEAR: i64 = 365 * 86400;
+
+// Supported year bases for converting APY into a per-second rate
+pub const YEAR_BASIS_360_SECONDS: i64 = 360 * 86400;
+pub const YEAR_BASIS_365_25_SECONDS: i64 = 36525 * 864;
+
+// Default slot estimate for slot-based accrual (400ms slots)
+pub const DEFAULT_SLOTS_PER_YEAR: u64 = 78_840_000;
+
+// Default window after staking in which a stake can be cancelled (24 hours)
+pub const DEFAULT_COOLING_OFF_SECONDS: i64 = 86400;
+
+// Default epoch length used to align accrual starts (1 day)
+pub const DEFAULT_EPOCH_SECONDS: i64 = 86400;
+
+// Minimum time between accrual checkpoint events for the same position (1 day)
+pub const ACCRUAL_CHECKPOINT_INTERVAL: i64 = 86400;
+
+// Default penalty on principal for exiting before unlock (10.00%)
+pub const DEFAULT_EARLY_EXIT_PENALTY_BPS: u16 = 1000;
+
+// Domain separation prefixes for the positions Merkle tree, so an internal node can never pass as a leaf
+pub const MERKLE_LEAF_PREFIX: u8 = 0;
+pub const MERKLE_NODE_PREFIX: u8 = 1;
+
 #[program]
 pub mod thc_staking {
     use super::*;
 
-    // Initialize the staking program
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+    // Initialize the staking program, naming the governance key that co-signs dual-authorized actions
+    pub fn initialize(ctx: Context<Initialize>, governance: Pubkey) -> Result<()> {
         let staking_authority = &mut ctx.accounts.staking_authority;
         
         // Set up authority account
         staking_authority.authority = ctx.accounts.authority.key();
+        staking_authority.assign_governance(governance)?;
         staking_authority.validator = Pubkey::from_str("5Mp3EF1donYwLxhe5hs6HoWpAucZGLZ76NKRNztkjEej").unwrap();
         staking_authority.token_mint = Pubkey::from_str("4kXPBvQthvpes9TC7h6tXsYxWPUbYWpocBMVUG3eBLy4").unwrap();
         staking_authority.rewards_pool = ctx.accounts.rewards_pool.key();
         staking_authority.total_staked = 0;
         staking_authority.staker_count = 0;
+        staking_authority.early_exit_penalty_bps = DEFAULT_EARLY_EXIT_PENALTY_BPS;
+        staking_authority.slots_per_year = DEFAULT_SLOTS_PER_YEAR;
+        staking_authority.cooling_off_seconds = DEFAULT_COOLING_OFF_SECONDS;
+        staking_authority.version = AUTHORITY_VERSION;
         
         // Initialize bumps
         staking_authority.bumps = AuthorityBumps {
@@ -33,35 +95,253 @@ pub mod thc_staking {
         if amount == 0 {
             return err!(StakingError::InvalidAmount);
         }
+        ctx.accounts.staking_authority.check_new_stakes_open()?;
 
         let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp;
-        
-        // Calculate unlock time based on lock period
-        let lock_period_seconds = (lock_period_days as i64) * 86400; // 86400 seconds = 1 day
-        let unlock_time = current_time + lock_period_seconds;
         
         // Determine APY based on lock period
-        let apy = match lock_period_days {
-            d if d >= 365 => 1500, // 15.00%
-            d if d >= 180 => 1200, // 12.00%
-            d if d >= 90 => 800,   // 8.00%
-            _ => 500,              // 5.00% default
-        };
+        let tier_index = tier_for_lock_period(lock_period_days);
+        let apy = ctx.accounts.staking_authority.tier_apy(tier_index as u8, clock.unix_timestamp);
+        ctx.accounts.staking_authority.check_tier_vault(tier_index as u8, &ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        ctx.accounts.staking_authority.check_unlock_horizon(
+            clock.unix_timestamp + lock_period_days as i64 * 86400,
+            clock.unix_timestamp,
+        )?;
+        
+        // Initialize stake account
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.owner = ctx.accounts.owner.key();
+        stake_account.stake_authority = ctx.accounts.staking_authority.key();
+        stake_account.token_account = ctx.accounts.token_account.key();
+        stake_account.staked_mint = ctx.accounts.staking_authority.token_mint;
+        stake_account.bump = *ctx.bumps.get("stake_account").unwrap();
+        stake_account.open(amount, lock_period_days, apy, ctx.accounts.staking_authority.year_seconds(), &clock);
+        stake_account.align_accrual_start(&ctx.accounts.staking_authority, &clock);
+        stake_account.sync_reward_index(&mut ctx.accounts.staking_authority, &clock);
+        
+        // Transfer tokens from user to staking vault
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.token_account.to_account_info(),
+                to: ctx.accounts.staking_vault.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        );
+        token::transfer(transfer_ctx, amount)?;
+        
+        // Update staking stats
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.record_position_opened(stake_account.tier_index, amount, stake_account.reward_liability);
+        
+        Ok(())
+    }
+
+    // Reserve a future stake, fixing the APY at today's rate until valid_until.
+    // While an APY auction is configured, a bonus on top decays from its maximum to zero over the auction.
+    pub fn reserve_stake(
+        ctx: Context<ReserveStake>,
+        amount: u64,
+        lock_period_days: u16,
+        valid_until: i64,
+    ) -> Result<()> {
+        if amount == 0 {
+            return err!(StakingError::InvalidAmount);
+        }
+        ctx.accounts.staking_authority.check_new_stakes_open()?;
+
+        let clock = Clock::get()?;
+        if valid_until <= clock.unix_timestamp {
+            return err!(StakingError::InvalidReservationExpiry);
+        }
+        
+        // Initialize reservation
+        let reservation = &mut ctx.accounts.reservation;
+        reservation.owner = ctx.accounts.owner.key();
+        reservation.stake_authority = ctx.accounts.staking_authority.key();
+        reservation.amount = amount;
+        reservation.lock_period_days = lock_period_days;
+        reservation.apy = ctx
+            .accounts
+            .staking_authority
+            .tier_apy(tier_for_lock_period(lock_period_days) as u8, clock.unix_timestamp);
+        reservation.created_at = clock.unix_timestamp;
+        reservation.valid_until = valid_until;
+        reservation.bump = *ctx.bumps.get("reservation").unwrap();
+        reservation.auction_bonus_bps = ctx.accounts.staking_authority.auction_max_bonus_bps;
+        reservation.auction_duration_seconds = ctx.accounts.staking_authority.auction_duration_seconds;
+        
+        Ok(())
+    }
+
+    // Fund a reservation, opening a stake at the reserved APY
+    pub fn fulfill_reservation(ctx: Context<FulfillReservation>) -> Result<()> {
+        ctx.accounts.staking_authority.check_new_stakes_open()?;
+
+        let clock = Clock::get()?;
+        
+        // Check if reservation is still valid
+        let reservation = &ctx.accounts.reservation;
+        if reservation.is_expired(clock.unix_timestamp) {
+            return err!(StakingError::ReservationExpired);
+        }
+        let amount = reservation.amount;
+        ctx.accounts.staking_authority.check_tier_vault(
+            tier_for_lock_period(reservation.lock_period_days) as u8,
+            &ctx.accounts.staking_vault.key(),
+        )?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        ctx.accounts.staking_authority.check_unlock_horizon(
+            clock.unix_timestamp + reservation.lock_period_days as i64 * 86400,
+            clock.unix_timestamp,
+        )?;
         
         // Initialize stake account
         let stake_account = &mut ctx.accounts.stake_account;
         stake_account.owner = ctx.accounts.owner.key();
         stake_account.stake_authority = ctx.accounts.staking_authority.key();
         stake_account.token_account = ctx.accounts.token_account.key();
-        stake_account.deposit_amount = amount;
-        stake_account.start_time = current_time;
-        stake_account.unlock_time = unlock_time;
-        stake_account.apy = apy;
-        stake_account.rewards_claimed = 0;
-        stake_account.last_claimed_time = current_time;
-        stake_account.is_active = true;
+        stake_account.staked_mint = ctx.accounts.staking_authority.token_mint;
         stake_account.bump = *ctx.bumps.get("stake_account").unwrap();
+        stake_account.open(
+            amount,
+            reservation.lock_period_days,
+            reservation.apy_at(clock.unix_timestamp),
+            ctx.accounts.staking_authority.year_seconds(),
+            &clock,
+        );
+        stake_account.align_accrual_start(&ctx.accounts.staking_authority, &clock);
+        stake_account.sync_reward_index(&mut ctx.accounts.staking_authority, &clock);
+        
+        // Transfer tokens from user to staking vault
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.token_account.to_account_info(),
+                to: ctx.accounts.staking_vault.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        );
+        token::transfer(transfer_ctx, amount)?;
+        
+        // Update staking stats
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.record_position_opened(stake_account.tier_index, amount, stake_account.reward_liability);
+        
+        Ok(())
+    }
+
+    // Configure the APY auction for new reservations: a bonus of up to auction_max_bonus_bps
+    // that decays linearly to zero over auction_duration_seconds, zero duration disables it
+    pub fn set_apy_auction(
+        ctx: Context<AdminOnly>,
+        auction_max_bonus_bps: u16,
+        auction_duration_seconds: i64,
+    ) -> Result<()> {
+        if auction_duration_seconds < 0 {
+            return err!(StakingError::InvalidAuctionDuration);
+        }
+
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.auction_max_bonus_bps = auction_max_bonus_bps;
+        staking_authority.auction_duration_seconds = auction_duration_seconds;
+        Ok(())
+    }
+
+    // Close a reservation and reclaim its rent
+    pub fn close_reservation(_ctx: Context<CloseReservation>) -> Result<()> {
+        Ok(())
+    }
+
+    // Close an expired reservation, refunding its rent to the owner, callable by any keeper
+    pub fn expire_reservation(ctx: Context<ExpireReservation>) -> Result<()> {
+        let clock = Clock::get()?;
+        
+        // Check the reservation can no longer be fulfilled
+        let reservation = &ctx.accounts.reservation;
+        if !reservation.is_expired(clock.unix_timestamp) {
+            return err!(StakingError::ReservationNotExpired);
+        }
+        
+        emit!(ReservationExpired {
+            reservation: reservation.key(),
+            owner: reservation.owner,
+            valid_until: reservation.valid_until,
+        });
+        
+        Ok(())
+    }
+
+    // Boost a stake's APY by proving a sufficiently aged position in the configured LP program
+    pub fn apply_lp_boost(ctx: Context<ApplyLpBoost>) -> Result<()> {
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        
+        let staking_authority = &ctx.accounts.staking_authority;
+        if staking_authority.lp_boost_bps == 0 {
+            return err!(StakingError::LpBoostDisabled);
+        }
+        
+        // Validate the LP position belongs to the staker and has been held long enough
+        let lp_position = LpPositionRecord::parse(&ctx.accounts.lp_position.try_borrow_data()?)?;
+        if lp_position.owner != ctx.accounts.owner.key() {
+            return err!(StakingError::InvalidLpPosition);
+        }
+        if clock.unix_timestamp - lp_position.opened_at < staking_authority.lp_min_age_seconds {
+            return err!(StakingError::LpPositionTooNew);
+        }
+        
+        // Settle rewards at the old rate before the boost applies
+        stake_account.owner_last_active = clock.unix_timestamp;
+        stake_account.settle_rewards(staking_authority, &clock);
+        let seconds_per_year = staking_authority.year_seconds();
+        let liability_before = stake_account.projected_rewards(stake_account.deposit_amount, clock.unix_timestamp, seconds_per_year);
+        stake_account.boost_bps = staking_authority.lp_boost_bps;
+        stake_account.refresh_accrual_rate(staking_authority);
+        let added_liability = stake_account
+            .projected_rewards(stake_account.deposit_amount, clock.unix_timestamp, seconds_per_year)
+            .saturating_sub(liability_before);
+        stake_account.reward_liability = stake_account.reward_liability.checked_add(added_liability).unwrap();
+        
+        // Update liability stats
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.committed_liability = staking_authority.committed_liability.checked_add(added_liability).unwrap();
+        
+        Ok(())
+    }
+
+    // Add THC tokens to an existing stake, keeping its lock and APY
+    pub fn increase_stake(ctx: Context<IncreaseStake>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return err!(StakingError::InvalidAmount);
+        }
+
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        if stake_account.unbonding {
+            return err!(StakingError::PositionUnbonding);
+        }
+        ctx.accounts.staking_authority.check_tier_vault(stake_account.tier_index, &ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        
+        // Rate-limit increases per position, each one settles and checkpoints accrual
+        stake_account.check_increase_interval(&ctx.accounts.staking_authority, clock.unix_timestamp)?;
+        
+        // Accrue the old principal up to now before it changes
+        stake_account.owner_last_active = clock.unix_timestamp;
+        stake_account.last_increased_at = clock.unix_timestamp;
+        stake_account.checkpoint_principal(&ctx.accounts.staking_authority, &clock);
         
         // Transfer tokens from user to staking vault
         let transfer_ctx = CpiContext::new(
@@ -74,37 +354,217 @@ pub mod thc_staking {
         );
         token::transfer(transfer_ctx, amount)?;
         
+        // Update stake account
+        stake_account.deposit_amount = stake_account.deposit_amount.checked_add(amount).unwrap();
+        let added_liability = stake_account.commit_liability(amount, clock.unix_timestamp, ctx.accounts.staking_authority.year_seconds());
+        stake_account.sync_reward_index(&mut ctx.accounts.staking_authority, &clock);
+        
         // Update staking stats
         let staking_authority = &mut ctx.accounts.staking_authority;
         staking_authority.total_staked = staking_authority.total_staked.checked_add(amount).unwrap();
-        staking_authority.staker_count = staking_authority.staker_count.checked_add(1).unwrap();
+        staking_authority.add_tier_stake(stake_account.tier_index, amount);
+        staking_authority.committed_liability = staking_authority.committed_liability.checked_add(added_liability).unwrap();
+        
+        Ok(())
+    }
+
+    // Push a stake's unlock time later, keeping its tier and APY
+    pub fn extend_lock(ctx: Context<ExtendLock>, new_unlock_time: i64) -> Result<()> {
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        if stake_account.unbonding {
+            return err!(StakingError::PositionUnbonding);
+        }
+        if new_unlock_time <= stake_account.unlock_time {
+            return err!(StakingError::InvalidLockExtension);
+        }
+        ctx.accounts.staking_authority.check_unlock_horizon(new_unlock_time, clock.unix_timestamp)?;
+        
+        // Commit the rewards the extra lock time will earn
+        let added_liability = stake_account.extend_unlock(
+            new_unlock_time,
+            clock.unix_timestamp,
+            ctx.accounts.staking_authority.year_seconds(),
+        );
+        stake_account.owner_last_active = clock.unix_timestamp;
+        
+        // Update liability stats
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.committed_liability = staking_authority.committed_liability.checked_add(added_liability).unwrap();
+        
+        Ok(())
+    }
+
+    // Move a stake to a longer lock tier, keeping its principal. Rewards settle at the old tier,
+    // the new tier's APY applies from now and the lock runs at least the new tier's minimum from now.
+    pub fn change_tier(ctx: Context<ChangeTier>, new_tier_index: u8) -> Result<()> {
+        let clock = Clock::get()?;
+        
+        // Check the stake can move to the new tier
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.check_tier_change(new_tier_index)?;
+        let old_tier_index = stake_account.tier_index;
+        
+        let new_unlock_time = stake_account.unlock_time_for_tier(new_tier_index, clock.unix_timestamp);
+        let staking_authority = &ctx.accounts.staking_authority;
+        staking_authority.check_tier_vault(old_tier_index, &ctx.accounts.staking_vault.key())?;
+        staking_authority.check_tier_vault(new_tier_index, &ctx.accounts.new_staking_vault.key())?;
+        check_vault_authority(staking_authority, &ctx.accounts.staking_vault)?;
+        check_vault_authority(staking_authority, &ctx.accounts.new_staking_vault)?;
+        staking_authority.check_unlock_horizon(new_unlock_time, clock.unix_timestamp)?;
+        
+        // Move principal when the tiers keep separate vaults
+        if ctx.accounts.staking_vault.key() != ctx.accounts.new_staking_vault.key() {
+            let staking_authority_seeds = &[
+                b"staking_authority".as_ref(),
+                staking_authority.token_mint.as_ref(),
+                &[staking_authority.bumps.staking_authority],
+            ];
+            let staking_authority_signer = &[&staking_authority_seeds[..]];
+            
+            let transfer_principal_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.staking_vault.to_account_info(),
+                    to: ctx.accounts.new_staking_vault.to_account_info(),
+                    authority: ctx.accounts.staking_authority.to_account_info(),
+                },
+                staking_authority_signer,
+            );
+            token::transfer(transfer_principal_ctx, stake_account.deposit_amount)?;
+        }
+        
+        // Settle rewards at the old tier before the new one applies
+        stake_account.move_to_tier(&mut ctx.accounts.staking_authority, new_tier_index, &clock);
+        
+        Ok(())
+    }
+
+    // Start unbonding an unlocked stake. Accrual stops here, rewards earned so far stay
+    // claimable, and the principal can be withdrawn with unstake once the cooldown ends.
+    pub fn request_unbond(ctx: Context<RequestUnbond>) -> Result<()> {
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        stake_account.begin_unbonding(&mut ctx.accounts.staking_authority, &clock)?;
+        stake_account.owner_last_active = clock.unix_timestamp;
         
         Ok(())
     }
 
     // Unstake THC tokens
     pub fn unstake(ctx: Context<Unstake>) -> Result<()> {
+        unstake_position(
+            &mut ctx.accounts.staking_authority,
+            &mut ctx.accounts.stake_account,
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.rewards_pool,
+            &ctx.accounts.token_account,
+            &ctx.accounts.token_program,
+        )
+    }
+
+    // Unstake to another token account of the staked mint, for when the owner's usual
+    // token account is closed or frozen and cannot receive the returned tokens
+    pub fn unstake_to(ctx: Context<UnstakeTo>) -> Result<()> {
+        ctx.accounts.staking_authority.check_unstake_destination(&ctx.accounts.destination)?;
+        unstake_position(
+            &mut ctx.accounts.staking_authority,
+            &mut ctx.accounts.stake_account,
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.rewards_pool,
+            &ctx.accounts.destination,
+            &ctx.accounts.token_program,
+        )
+    }
+
+    // Admin-assisted recovery of a position whose stored bump does not derive its address, which the
+    // seeds constraints of every owner instruction reject. If some bump derives the address from the
+    // stored owner the bump is restored and the owner's instructions work again. Otherwise the
+    // position gets a rescue unstake, principal and rewards only going to the owner's token account.
+    pub fn recover_position(ctx: Context<RecoverPosition>) -> Result<()> {
         let clock = Clock::get()?;
-        let current_time = clock.unix_timestamp;
         
-        // Check if staking period has ended
+        // Positions reachable through the normal seeds must use unstake
         let stake_account = &mut ctx.accounts.stake_account;
-        if current_time < stake_account.unlock_time {
+        let stake_account_key = stake_account.key();
+        if stake_account.restore_bump(
+            &stake_account_key,
+            &ctx.accounts.staking_authority.key(),
+            &ctx.accounts.staking_authority.token_mint,
+        )? {
+            emit!(PositionBumpRestored {
+                stake_account: stake_account_key,
+                owner: stake_account.owner,
+                bump: stake_account.bump,
+            });
+            return Ok(());
+        }
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        if clock.unix_timestamp < stake_account.unlock_time {
             return err!(StakingError::StakingPeriodNotEnded);
         }
         
+        // The position could never request_unbond, so the rescue waives the cooldown
+        if ctx.accounts.staking_authority.unbonding_seconds > 0 && !stake_account.unbonding {
+            stake_account.settle_pending(&mut ctx.accounts.staking_authority, &clock);
+            stake_account.unbonding = true;
+            stake_account.cooldown_end = clock.unix_timestamp;
+        }
+        
+        let principal = stake_account.deposit_amount;
+        unstake_position(
+            &mut ctx.accounts.staking_authority,
+            &mut ctx.accounts.stake_account,
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.rewards_pool,
+            &ctx.accounts.token_account,
+            &ctx.accounts.token_program,
+        )?;
+        
+        emit!(PositionRecovered {
+            stake_account: ctx.accounts.stake_account.key(),
+            owner: ctx.accounts.stake_account.owner,
+            principal,
+        });
+        
+        Ok(())
+    }
+
+    // Exit a stake before its unlock time, forfeiting rewards and paying the early exit penalty
+    pub fn emergency_unstake(ctx: Context<Unstake>) -> Result<()> {
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp;
+        
         // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
         if !stake_account.is_active {
             return err!(StakingError::InactiveStake);
         }
         
-        // Calculate rewards
-        let time_staked = current_time - stake_account.last_claimed_time;
-        let time_staked_years = time_staked as f64 / (365.0 * 86400.0);
-        let apy_decimal = stake_account.apy as f64 / 10000.0;
-        let rewards = (stake_account.deposit_amount as f64 * apy_decimal * time_staked_years) as u64;
+        ctx.accounts.staking_authority.check_tier_vault(stake_account.tier_index, &ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        
+        stake_account.check_early_exit(current_time)?;
+        
+        // Calculate penalty on principal
+        let penalty = early_exit_penalty(
+            stake_account.deposit_amount,
+            ctx.accounts.staking_authority.early_exit_penalty_bps,
+        );
+        let refund = stake_account.deposit_amount.checked_sub(penalty).unwrap();
         
-        // Transfer principal back to user
         let staking_authority_seeds = &[
             b"staking_authority".as_ref(),
             ctx.accounts.staking_authority.token_mint.as_ref(),
@@ -112,66 +572,67 @@ pub mod thc_staking {
         ];
         let staking_authority_signer = &[&staking_authority_seeds[..]];
         
-        let transfer_principal_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.staking_vault.to_account_info(),
-                to: ctx.accounts.token_account.to_account_info(),
-                authority: ctx.accounts.staking_authority.to_account_info(),
-            },
-            staking_authority_signer,
-        );
-        token::transfer(transfer_principal_ctx, stake_account.deposit_amount)?;
-        
-        // Transfer rewards if any
-        if rewards > 0 {
-            let transfer_rewards_ctx = CpiContext::new_with_signer(
+        // Transfer remaining principal back to user
+        if refund > 0 {
+            let transfer_principal_ctx = CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 Transfer {
-                    from: ctx.accounts.rewards_pool.to_account_info(),
+                    from: ctx.accounts.staking_vault.to_account_info(),
                     to: ctx.accounts.token_account.to_account_info(),
                     authority: ctx.accounts.staking_authority.to_account_info(),
                 },
                 staking_authority_signer,
             );
-            token::transfer(transfer_rewards_ctx, rewards)?;
+            token::transfer(transfer_principal_ctx, refund)?;
+        }
+        
+        // Penalty goes to the rewards pool
+        if penalty > 0 {
+            let transfer_penalty_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.staking_vault.to_account_info(),
+                    to: ctx.accounts.rewards_pool.to_account_info(),
+                    authority: ctx.accounts.staking_authority.to_account_info(),
+                },
+                staking_authority_signer,
+            );
+            token::transfer(transfer_penalty_ctx, penalty)?;
         }
         
         // Update stake account
         stake_account.is_active = false;
-        stake_account.rewards_claimed = stake_account.rewards_claimed.checked_add(rewards).unwrap();
+        stake_account.sync_reward_index(&mut ctx.accounts.staking_authority, &clock);
+        let released_liability = stake_account.reward_liability;
+        stake_account.reward_liability = 0;
         
         // Update staking stats
         let staking_authority = &mut ctx.accounts.staking_authority;
-        staking_authority.total_staked = staking_authority.total_staked.checked_sub(stake_account.deposit_amount).unwrap();
-        staking_authority.staker_count = staking_authority.staker_count.checked_sub(1).unwrap();
+        staking_authority.record_position_closed(stake_account.tier_index, stake_account.deposit_amount, released_liability);
         
         Ok(())
     }
 
-    // Claim rewards without unstaking
-    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+    // Cancel a stake within the cooling-off window, refunding the full principal without rewards or penalty
+    pub fn cancel_recent_stake(ctx: Context<CancelRecentStake>) -> Result<()> {
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp;
         
         // Check if stake is active
-        let stake_account = &mut ctx.accounts.stake_account;
+        let stake_account = &ctx.accounts.stake_account;
         if !stake_account.is_active {
             return err!(StakingError::InactiveStake);
         }
         
-        // Calculate rewards
-        let time_staked = current_time - stake_account.last_claimed_time;
-        let time_staked_years = time_staked as f64 / (365.0 * 86400.0);
-        let apy_decimal = stake_account.apy as f64 / 10000.0;
-        let rewards = (stake_account.deposit_amount as f64 * apy_decimal * time_staked_years) as u64;
+        ctx.accounts.staking_authority.check_tier_vault(stake_account.tier_index, &ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
         
-        // Check if rewards are available
-        if rewards == 0 {
-            return err!(StakingError::NoRewardsAvailable);
+        // Check if still within the cooling-off window
+        if !stake_account.in_cooling_off(&ctx.accounts.staking_authority, current_time) {
+            return err!(StakingError::CoolingOffExpired);
         }
         
-        // Transfer rewards to user
+        // Transfer principal back to user
         let staking_authority_seeds = &[
             b"staking_authority".as_ref(),
             ctx.accounts.staking_authority.token_mint.as_ref(),
@@ -179,314 +640,6129 @@ pub mod thc_staking {
         ];
         let staking_authority_signer = &[&staking_authority_seeds[..]];
         
-        let transfer_rewards_ctx = CpiContext::new_with_signer(
+        let transfer_principal_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             Transfer {
-                from: ctx.accounts.rewards_pool.to_account_info(),
+                from: ctx.accounts.staking_vault.to_account_info(),
                 to: ctx.accounts.token_account.to_account_info(),
                 authority: ctx.accounts.staking_authority.to_account_info(),
             },
             staking_authority_signer,
         );
-        token::transfer(transfer_rewards_ctx, rewards)?;
+        token::transfer(transfer_principal_ctx, stake_account.deposit_amount)?;
         
-        // Update stake account
-        stake_account.rewards_claimed = stake_account.rewards_claimed.checked_add(rewards).unwrap();
-        stake_account.last_claimed_time = current_time;
+        // Leave the reward index, forfeiting index rewards
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.is_active = false;
+        stake_account.sync_reward_index(&mut ctx.accounts.staking_authority, &clock);
+        
+        // Update staking stats, the stake account itself is closed
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.record_position_closed(stake_account.tier_index, stake_account.deposit_amount, stake_account.reward_liability);
         
         Ok(())
     }
-    
-    // Get staking stats
-    pub fn get_staking_stats(ctx: Context<GetStakingStats>) -> Result<StakingStatsResult> {
-        let staking_authority = &ctx.accounts.staking_authority;
-        
-        // Return staking stats
-        Ok(StakingStatsResult {
-            total_staked: staking_authority.total_staked,
-            staker_count: staking_authority.staker_count,
-            validator: staking_authority.validator,
-            apy_tiers: vec![
-                ApyTier { period_days: 30, apy_bps: 500 },
-                ApyTier { period_days: 90, apy_bps: 800 },
-                ApyTier { period_days: 180, apy_bps: 1200 },
-                ApyTier { period_days: 365, apy_bps: 1500 }
-            ]
-        })
+
+    // Record a negotiated APY band on a position, the rate it accrues at is kept within
+    // [min_apy, max_apy] whatever the global rate does. Zero on either side leaves it open.
+    pub fn set_apy_band(ctx: Context<SetApyBand>, min_apy: u16, max_apy: u16) -> Result<()> {
+        if max_apy > 0 && min_apy > max_apy {
+            return err!(StakingError::InvalidApyBand);
+        }
+
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        
+        // Settle rewards at the old band before the new one applies
+        stake_account.settle_pending(&mut ctx.accounts.staking_authority, &clock);
+        let seconds_per_year = ctx.accounts.staking_authority.year_seconds();
+        let liability_before = stake_account.projected_rewards(stake_account.deposit_amount, clock.unix_timestamp, seconds_per_year);
+        stake_account.min_apy = min_apy;
+        stake_account.max_apy = max_apy;
+        stake_account.refresh_accrual_rate(&ctx.accounts.staking_authority);
+        let liability_after = stake_account.projected_rewards(stake_account.deposit_amount, clock.unix_timestamp, seconds_per_year);
+        
+        // Update liability stats
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if liability_after >= liability_before {
+            let added_liability = liability_after - liability_before;
+            stake_account.reward_liability = stake_account.reward_liability.checked_add(added_liability).unwrap();
+            staking_authority.committed_liability = staking_authority.committed_liability.checked_add(added_liability).unwrap();
+        } else {
+            let released_liability = stake_account.release_liability(liability_before - liability_after);
+            staking_authority.committed_liability = staking_authority.committed_liability.saturating_sub(released_liability);
+        }
+        
+        Ok(())
+    }
+
+    // Opt a stake in or out of keeper-driven auto-compounding
+    pub fn set_auto_compound(ctx: Context<UpdateStake>, enabled: bool) -> Result<()> {
+        let clock = Clock::get()?;
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.auto_compound = enabled;
+        stake_account.owner_last_active = clock.unix_timestamp;
+        Ok(())
     }
     
-    // Calculate available rewards for a stake account
-    pub fn calculate_rewards(ctx: Context<CalculateRewards>) -> Result<RewardsResult> {
+    // Opt a stake in or out of relocking for its lock duration from the time of each paying owner claim
+    pub fn set_auto_relock_on_claim(ctx: Context<UpdateStake>, enabled: bool) -> Result<()> {
+        let clock = Clock::get()?;
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.auto_relock_on_claim = enabled;
+        stake_account.owner_last_active = clock.unix_timestamp;
+        Ok(())
+    }
+    
+    // Let another signer claim rewards to the owner's token account, None removes it
+    pub fn set_claim_delegate(ctx: Context<UpdateStake>, claim_delegate: Option<Pubkey>) -> Result<()> {
+        let clock = Clock::get()?;
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.claim_delegate = claim_delegate;
+        stake_account.owner_last_active = clock.unix_timestamp;
+        Ok(())
+    }
+    
+    // Designate who can take over the position once the owner has been inactive, None removes it
+    pub fn set_beneficiary(ctx: Context<UpdateStake>, beneficiary: Option<Pubkey>) -> Result<()> {
+        let clock = Clock::get()?;
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.beneficiary = beneficiary;
+        stake_account.owner_last_active = clock.unix_timestamp;
+        Ok(())
+    }
+    
+    // Start unbonding an unlocked position on behalf of an inactive owner, so the beneficiary can
+    // claim it once the cooldown ends
+    pub fn request_unbond_as_beneficiary(ctx: Context<BeneficiaryUnbond>) -> Result<()> {
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        stake_account.check_owner_inactive(&ctx.accounts.staking_authority, clock.unix_timestamp)?;
+        stake_account.begin_unbonding(&mut ctx.accounts.staking_authority, &clock)?;
+        
+        Ok(())
+    }
+    
+    // Pay out an unlocked position to its beneficiary after the owner has been inactive
+    // for longer than the configured threshold
+    pub fn claim_as_beneficiary(ctx: Context<ClaimAsBeneficiary>) -> Result<()> {
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp;
         
-        // Get stake account
-        let stake_account = &ctx.accounts.stake_account;
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
         if !stake_account.is_active {
-            return Ok(RewardsResult {
-                available_rewards: 0,
-                apy: stake_account.apy,
-                time_staked: 0,
-                unlock_time: stake_account.unlock_time,
-                current_time
+            return err!(StakingError::InactiveStake);
+        }
+        
+        // Check the owner has been inactive long enough
+        stake_account.check_owner_inactive(&ctx.accounts.staking_authority, current_time)?;
+        
+        // The lock and the unbonding cooldown still apply to the beneficiary
+        if current_time < stake_account.unlock_time {
+            return err!(StakingError::StakingPeriodNotEnded);
+        }
+        stake_account.check_cooldown_ended(&ctx.accounts.staking_authority, current_time)?;
+        ctx.accounts.staking_authority.check_tier_vault(stake_account.tier_index, &ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        
+        // Calculate rewards, forfeiting pre-cap rewards once the grace window has closed
+        stake_account.sync_reward_index(&mut ctx.accounts.staking_authority, &clock);
+        stake_account.settle_before_cap(&ctx.accounts.staking_authority, &clock);
+        let rewards = if ctx.accounts.staking_authority.rewards_claimable(current_time) {
+            stake_account
+                .pending_rewards(&ctx.accounts.staking_authority, &clock)
+                .min(ctx.accounts.rewards_pool.amount)
+        } else {
+            0
+        };
+        
+        // Transfer principal to the beneficiary
+        let staking_authority_seeds = &[
+            b"staking_authority".as_ref(),
+            ctx.accounts.staking_authority.token_mint.as_ref(),
+            &[ctx.accounts.staking_authority.bumps.staking_authority],
+        ];
+        let staking_authority_signer = &[&staking_authority_seeds[..]];
+        
+        let transfer_principal_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.staking_vault.to_account_info(),
+                to: ctx.accounts.beneficiary_token_account.to_account_info(),
+                authority: ctx.accounts.staking_authority.to_account_info(),
+            },
+            staking_authority_signer,
+        );
+        token::transfer(transfer_principal_ctx, stake_account.deposit_amount)?;
+        
+        // Transfer rewards if any
+        if rewards > 0 {
+            let transfer_rewards_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.rewards_pool.to_account_info(),
+                    to: ctx.accounts.beneficiary_token_account.to_account_info(),
+                    authority: ctx.accounts.staking_authority.to_account_info(),
+                },
+                staking_authority_signer,
+            );
+            token::transfer(transfer_rewards_ctx, rewards)?;
+        }
+        
+        // Update stake account
+        stake_account.is_active = false;
+        stake_account.sync_reward_index(&mut ctx.accounts.staking_authority, &clock);
+        let released_liability = stake_account.reward_liability;
+        stake_account.reward_liability = 0;
+        stake_account.rewards_claimed = stake_account.rewards_claimed.checked_add(rewards).unwrap();
+        stake_account.record_claim(rewards, false, &clock);
+        
+        // Update staking stats
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.record_position_closed(stake_account.tier_index, stake_account.deposit_amount, released_liability);
+        staking_authority.record_emission(rewards, &clock);
+        staking_authority.record_tier_emission(stake_account.tier_index, rewards, &clock);
+        
+        emit!(BeneficiaryClaimed {
+            stake_account: stake_account.key(),
+            owner: stake_account.owner,
+            beneficiary: ctx.accounts.beneficiary.key(),
+            principal: stake_account.deposit_amount,
+            rewards,
+        });
+        
+        Ok(())
+    }
+
+    // Restake an auto-compounding position's pending rewards, callable by any keeper
+    pub fn crank_compound(ctx: Context<CrankCompound>) -> Result<()> {
+        let clock = Clock::get()?;
+        
+        // Check if stake is active and opted in
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        if !stake_account.auto_compound {
+            return err!(StakingError::AutoCompoundDisabled);
+        }
+        ctx.accounts.staking_authority.check_tier_vault(stake_account.tier_index, &ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        
+        // Rate-limit compounding per position
+        if clock.unix_timestamp - stake_account.last_compounded_at
+            < ctx.accounts.staking_authority.min_compound_interval
+        {
+            return err!(StakingError::CompoundTooSoon);
+        }
+        
+        // Calculate rewards
+        let pending = stake_account.settle_pending(&mut ctx.accounts.staking_authority, &clock);
+        if pending == 0 {
+            return err!(StakingError::NoRewardsAvailable);
+        }
+        if !ctx.accounts.staking_authority.rewards_claimable(clock.unix_timestamp) {
+            return err!(StakingError::EmissionGraceExpired);
+        }
+        
+        // Keep the pool's reserve, the rest stays owed
+        let amount = ctx.accounts.staking_authority.max_payout_within_reserve(
+            ctx.accounts.rewards_pool.amount,
+            stake_account.reward_liability,
+            pending,
+        );
+        
+        restake_rewards(
+            &mut ctx.accounts.staking_authority,
+            stake_account,
+            &ctx.accounts.rewards_pool,
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.token_program,
+            amount,
+            &clock,
+        )?;
+        stake_account.last_compounded_at = clock.unix_timestamp;
+        
+        emit!(Compounded {
+            stake_account: stake_account.key(),
+            amount,
+            deposit_amount: stake_account.deposit_amount,
+        });
+        
+        Ok(())
+    }
+
+    // Emit a position's accrued-to-now rewards for off-chain accounting, callable by any keeper
+    // at most once per ACCRUAL_CHECKPOINT_INTERVAL. The accrual window is folded into the settled
+    // balances, which leaves what the position can claim unchanged.
+    pub fn emit_accrual_checkpoint(ctx: Context<EmitAccrualCheckpoint>) -> Result<()> {
+        let clock = Clock::get()?;
+        let staking_authority = &ctx.accounts.staking_authority;
+        let stake_account = &mut ctx.accounts.stake_account;
+        let accrued = stake_account.checkpoint_accrual(staking_authority, &clock)?;
+        
+        emit!(AccrualCheckpoint {
+            stake_account: stake_account.key(),
+            owner: stake_account.owner,
+            deposit_amount: stake_account.deposit_amount,
+            accrued,
+            entitled_rewards: stake_account.entitled_rewards(staking_authority, &clock),
+            rewards_claimed: stake_account.rewards_claimed,
+            timestamp: clock.unix_timestamp,
+        });
+        
+        Ok(())
+    }
+
+
+    // Claim rewards without unstaking, signed by the owner or their claim delegate.
+    // Rewards always go to the owner's token account.
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        ctx.accounts.staking_authority.check_fee_collector(ctx.accounts.fee_collector.as_ref().map(|fee_collector| fee_collector.key()))?;
+        
+        // Calculate rewards, freezing anything earned before the emission cap
+        if ctx.accounts.claimer.key() == stake_account.owner {
+            stake_account.owner_last_active = clock.unix_timestamp;
+        }
+        let pending = stake_account.settle_pending(&mut ctx.accounts.staking_authority, &clock);
+        
+        // Check if rewards are available
+        if pending == 0 {
+            return err!(StakingError::NoRewardsAvailable);
+        }
+        
+        // Dust claims wait until more rewards accumulate, unstake always settles
+        ctx.accounts.staking_authority.check_min_claim(pending)?;
+        
+        // Cap the payout per epoch, the excess stays owed for later epochs
+        let epoch_allowance = stake_account.epoch_claim_allowance(&ctx.accounts.staking_authority, clock.unix_timestamp);
+        if epoch_allowance == 0 {
+            return err!(StakingError::EpochClaimCapReached);
+        }
+        let pending = pending.min(epoch_allowance);
+        
+        // Pre-cap rewards can only be claimed during the grace window
+        if !ctx.accounts.staking_authority.rewards_claimable(clock.unix_timestamp) {
+            return err!(StakingError::EmissionGraceExpired);
+        }
+        
+        // Throttle the claim so the pool keeps its reserve, leaving the rest owed
+        let pool_rewards = ctx.accounts.staking_authority.max_payout_within_reserve(
+            ctx.accounts.rewards_pool.amount,
+            stake_account.reward_liability,
+            pending,
+        );
+        
+        // Cover the shortfall from the backstop, up to its remaining hard cap
+        let backstop_rewards = match &ctx.accounts.reward_backstop {
+            Some(reward_backstop) => ctx
+                .accounts
+                .staking_authority
+                .backstop_remaining()
+                .min(reward_backstop.amount)
+                .min(pending - pool_rewards),
+            None => 0,
+        };
+        let rewards = pool_rewards + backstop_rewards;
+        if rewards < pending {
+            emit!(ReserveFloorReached {
+                stake_account: stake_account.key(),
+                requested: pending,
+                paid: rewards,
+                rewards_pool_balance: ctx.accounts.rewards_pool.amount,
+                committed_liability: ctx.accounts.staking_authority.committed_liability,
             });
         }
         
-        // Calculate time staked
-        let time_staked = current_time - stake_account.last_claimed_time;
-        let time_staked_years = time_staked as f64 / (365.0 * 86400.0);
-        let apy_decimal = stake_account.apy as f64 / 10000.0;
-        let rewards = (stake_account.deposit_amount as f64 * apy_decimal * time_staked_years) as u64;
+        if rewards > 0 {
+            // Transfer rewards to user
+            let staking_authority_seeds = &[
+                b"staking_authority".as_ref(),
+                ctx.accounts.staking_authority.token_mint.as_ref(),
+                &[ctx.accounts.staking_authority.bumps.staking_authority],
+            ];
+            let staking_authority_signer = &[&staking_authority_seeds[..]];
+            
+            if pool_rewards > 0 {
+                let transfer_rewards_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.rewards_pool.to_account_info(),
+                        to: ctx.accounts.token_account.to_account_info(),
+                        authority: ctx.accounts.staking_authority.to_account_info(),
+                    },
+                    staking_authority_signer,
+                );
+                token::transfer(transfer_rewards_ctx, pool_rewards)?;
+            }
+            
+            if let (Some(reward_backstop), true) = (&ctx.accounts.reward_backstop, backstop_rewards > 0) {
+                let transfer_backstop_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: reward_backstop.to_account_info(),
+                        to: ctx.accounts.token_account.to_account_info(),
+                        authority: ctx.accounts.staking_authority.to_account_info(),
+                    },
+                    staking_authority_signer,
+                );
+                token::transfer(transfer_backstop_ctx, backstop_rewards)?;
+                
+                let staking_authority = &mut ctx.accounts.staking_authority;
+                staking_authority.backstop_used = staking_authority.backstop_used.checked_add(backstop_rewards).unwrap();
+                emit!(RewardBackstopUsed {
+                    stake_account: stake_account.key(),
+                    amount: backstop_rewards,
+                    backstop_used: staking_authority.backstop_used,
+                    backstop_cap: staking_authority.backstop_cap,
+                });
+            }
+            
+            // Charge the claim fee in SOL to fund the crank, paid by whoever signed the claim
+            let claim_fee_lamports = ctx.accounts.staking_authority.claim_fee_due(rewards);
+            if let (Some(fee_collector), true) = (&ctx.accounts.fee_collector, claim_fee_lamports > 0) {
+                let transfer_fee_ctx = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.claimer.to_account_info(),
+                        to: fee_collector.to_account_info(),
+                    },
+                );
+                system_program::transfer(transfer_fee_ctx, claim_fee_lamports)?;
+            }
+        }
         
-        // Return rewards result
-        Ok(RewardsResult {
-            available_rewards: rewards,
-            apy: stake_account.apy,
-            time_staked,
-            unlock_time: stake_account.unlock_time,
-            current_time
-        })
+        // Update stake account
+        stake_account.take_rewards(rewards, &clock);
+        stake_account.record_claim(rewards, false, &clock);
+        stake_account.count_interim_claim(rewards, &clock);
+        stake_account.record_epoch_claim(&ctx.accounts.staking_authority, rewards, clock.unix_timestamp);
+        let released_liability = stake_account.release_liability(rewards);
+        
+        // Re-commit the position for another term when it locks on claim, whoever signed the claim
+        let added_liability = stake_account.relock_on_claim(&ctx.accounts.staking_authority, rewards, clock.unix_timestamp)?;
+        
+        // Update liability stats
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.committed_liability = staking_authority
+            .committed_liability
+            .saturating_sub(released_liability)
+            .checked_add(added_liability)
+            .unwrap();
+        
+        // Update emission stats
+        staking_authority.record_emission(rewards, &clock);
+        staking_authority.record_tier_emission(stake_account.tier_index, rewards, &clock);
+        
+        Ok(())
     }
-}
+    
+    // Pay out claim_bps of pending rewards and restake the remainder into the position
+    pub fn claim_and_compound(ctx: Context<ClaimAndCompound>, claim_bps: u16) -> Result<()> {
+        if claim_bps > 10000 {
+            return err!(StakingError::InvalidClaimSplit);
+        }
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        ctx.accounts.staking_authority.check_tier_vault(stake_account.tier_index, &ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        ctx.accounts.staking_authority.check_fee_collector(ctx.accounts.fee_collector.as_ref().map(|fee_collector| fee_collector.key()))?;
+        
+        // Calculate rewards
+        stake_account.owner_last_active = clock.unix_timestamp;
+        let pending = stake_account.settle_pending(&mut ctx.accounts.staking_authority, &clock);
+        if pending == 0 {
+            return err!(StakingError::NoRewardsAvailable);
+        }
+        
+        if !ctx.accounts.staking_authority.rewards_claimable(clock.unix_timestamp) {
+            return err!(StakingError::EmissionGraceExpired);
+        }
+        let (claimed, compounded) = stake_account.claim_split(
+            &ctx.accounts.staking_authority,
+            pending,
+            ctx.accounts.rewards_pool.amount,
+            claim_bps,
+            clock.unix_timestamp,
+        )?;
+        
+        if claimed > 0 {
+            // Transfer the claimed share to user
+            let staking_authority_seeds = &[
+                b"staking_authority".as_ref(),
+                ctx.accounts.staking_authority.token_mint.as_ref(),
+                &[ctx.accounts.staking_authority.bumps.staking_authority],
+            ];
+            let staking_authority_signer = &[&staking_authority_seeds[..]];
+            
+            let transfer_rewards_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.rewards_pool.to_account_info(),
+                    to: ctx.accounts.token_account.to_account_info(),
+                    authority: ctx.accounts.staking_authority.to_account_info(),
+                },
+                staking_authority_signer,
+            );
+            token::transfer(transfer_rewards_ctx, claimed)?;
+            
+            // Charge the claim fee in SOL on the paid-out share, as claim_rewards does
+            let claim_fee_lamports = ctx.accounts.staking_authority.claim_fee_due(claimed);
+            if let (Some(fee_collector), true) = (&ctx.accounts.fee_collector, claim_fee_lamports > 0) {
+                let transfer_fee_ctx = CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.owner.to_account_info(),
+                        to: fee_collector.to_account_info(),
+                    },
+                );
+                system_program::transfer(transfer_fee_ctx, claim_fee_lamports)?;
+            }
+            
+            // Update stake account
+            stake_account.take_rewards(claimed, &clock);
+            stake_account.record_claim(claimed, false, &clock);
+            stake_account.count_interim_claim(claimed, &clock);
+            stake_account.record_epoch_claim(&ctx.accounts.staking_authority, claimed, clock.unix_timestamp);
+            let released_liability = stake_account.release_liability(claimed);
+            
+            // Update liability and emission stats
+            let staking_authority = &mut ctx.accounts.staking_authority;
+            staking_authority.committed_liability = staking_authority.committed_liability.saturating_sub(released_liability);
+            staking_authority.record_emission(claimed, &clock);
+            staking_authority.record_tier_emission(stake_account.tier_index, claimed, &clock);
+        }
+        
+        // Restake the remainder
+        restake_rewards(
+            &mut ctx.accounts.staking_authority,
+            stake_account,
+            &ctx.accounts.rewards_pool,
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.token_program,
+            compounded,
+            &clock,
+        )?;
+        
+        // Re-commit the position for another term when it locks on claim
+        let added_liability =
+            stake_account.relock_on_claim(&ctx.accounts.staking_authority, claimed + compounded, clock.unix_timestamp)?;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.committed_liability = staking_authority.committed_liability.checked_add(added_liability).unwrap();
+        
+        Ok(())
+    }
     
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + StakingAuthority::SIZE,
-        seeds = [b"staking_authority", Pubkey::from_str("4kXPBvQthvpes9TC7h6tXsYxWPUbYWpocBMVUG3eBLy4").unwrap().as_ref()],
-        bump,
-    )]
-    pub staking_authority: Account<'info, StakingAuthority>,
+    // Manually correct total_staked after tokens were moved into or out of the vault directly
+    pub fn sync_total_staked(ctx: Context<AdminOnly>, delta: i64) -> Result<()> {
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        let old_total_staked = staking_authority.total_staked;
+        
+        // Apply the signed correction, rejecting underflow and overflow
+        let new_total_staked = match apply_total_staked_delta(old_total_staked, delta) {
+            Some(total) => total,
+            None => return err!(StakingError::TotalStakedOutOfBounds),
+        };
+        staking_authority.total_staked = new_total_staked;
+        
+        emit!(TotalStakedSynced {
+            delta,
+            old_total_staked,
+            new_total_staked,
+        });
+        
+        Ok(())
+    }
     
-    /// CHECK: This account is validated in the instruction
-    pub rewards_pool: AccountInfo<'info>,
+    // Set the index emission rate, settling the reward index at the old rate first so
+    // the change only applies from now on
+    pub fn set_reward_rate(ctx: Context<AdminOnly>, reward_per_second: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        let old_reward_per_second = staking_authority.change_reward_rate(reward_per_second, clock.unix_timestamp);
+        
+        emit!(RewardRateUpdated {
+            old_reward_per_second,
+            new_reward_per_second: reward_per_second,
+            acc_reward_per_share: staking_authority.acc_reward_per_share,
+        });
+        
+        Ok(())
+    }
     
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct Stake<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    // Set the slots-per-year estimate used for slot-based accrual, zero falls back to timestamps
+    pub fn set_slots_per_year(ctx: Context<AdminOnly>, slots_per_year: u64) -> Result<()> {
+        ctx.accounts.staking_authority.slots_per_year = slots_per_year;
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
-        bump = staking_authority.bumps.staking_authority,
-    )]
-    pub staking_authority: Account<'info, StakingAuthority>,
+    // Configure the lifetime reward emission cap and the grace window for claiming pre-cap rewards
+    pub fn set_emission_cap(ctx: Context<AdminOnly>, emission_cap: u64, cap_grace_seconds: i64) -> Result<()> {
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        
+        // Accrual has already been frozen at the cap
+        if staking_authority.emission_cap_reached() {
+            return err!(StakingError::EmissionCapReached);
+        }
+        
+        if cap_grace_seconds < 0 {
+            return err!(StakingError::InvalidGraceWindow);
+        }
+        
+        staking_authority.emission_cap = emission_cap;
+        staking_authority.cap_grace_seconds = cap_grace_seconds;
+        
+        // A cap at or below what was already emitted takes effect immediately
+        let clock = Clock::get()?;
+        staking_authority.record_emission(0, &clock);
+        
+        Ok(())
+    }
     
-    #[account(
-        init,
-        payer = owner,
-        space = 8 + StakeAccount::SIZE,
-        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
-        bump,
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
+    // Toggle topping off rewards at unstake so positions always receive the advertised APY
+    pub fn set_guarantee_advertised_apy(ctx: Context<AdminOnly>, enabled: bool) -> Result<()> {
+        ctx.accounts.staking_authority.guarantee_advertised_apy = enabled;
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        constraint = token_account.owner == owner.key(),
-        constraint = token_account.mint == staking_authority.token_mint,
-    )]
-    pub token_account: Account<'info, TokenAccount>,
+    // Configure the SOL fee charged on reward claims and the account that collects it
+    pub fn set_claim_fee(ctx: Context<AdminOnly>, claim_fee_lamports: u64, fee_collector: Pubkey) -> Result<()> {
+        // The default key is the system program, which can never be credited
+        if claim_fee_lamports > 0 && fee_collector == Pubkey::default() {
+            return err!(StakingError::InvalidFeeCollector);
+        }
+        
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.claim_fee_lamports = claim_fee_lamports;
+        staking_authority.fee_collector = fee_collector;
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        constraint = staking_vault.mint == staking_authority.token_mint,
-    )]
-    pub staking_vault: Account<'info, TokenAccount>,
+    // Hand the governance role to a new key, signed by the current governance
+    pub fn rotate_governance(ctx: Context<GovernanceOnly>, new_governance: Pubkey) -> Result<()> {
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        let old_governance = staking_authority.governance;
+        staking_authority.assign_governance(new_governance)?;
+        
+        emit!(GovernanceRotated {
+            old_governance,
+            new_governance,
+        });
+        
+        Ok(())
+    }
     
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
+    // Return over-distributed rewards from a user's token account to the rewards pool.
+    // Requires admin and governance signatures plus the user's approval of the staking authority as delegate.
+    pub fn clawback_rewards(ctx: Context<ClawbackRewards>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return err!(StakingError::InvalidAmount);
+        }
 
-#[derive(Accounts)]
-pub struct Unstake<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
+        let clock = Clock::get()?;
+        
+        let stake_account = &mut ctx.accounts.stake_account;
+        let over_distributed = stake_account.over_distributed(&ctx.accounts.staking_authority, &clock);
+        if amount > over_distributed {
+            return err!(StakingError::ClawbackExceedsOverDistribution);
+        }
+        
+        // The user consents by delegating the amount to the staking authority
+        let token_account = &ctx.accounts.token_account;
+        if token_account.delegate != COption::Some(ctx.accounts.staking_authority.key())
+            || token_account.delegated_amount < amount
+        {
+            return err!(StakingError::ClawbackNotApproved);
+        }
+        
+        let staking_authority_seeds = &[
+            b"staking_authority".as_ref(),
+            ctx.accounts.staking_authority.token_mint.as_ref(),
+            &[ctx.accounts.staking_authority.bumps.staking_authority],
+        ];
+        let staking_authority_signer = &[&staking_authority_seeds[..]];
+        
+        let transfer_clawback_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.token_account.to_account_info(),
+                to: ctx.accounts.rewards_pool.to_account_info(),
+                authority: ctx.accounts.staking_authority.to_account_info(),
+            },
+            staking_authority_signer,
+        );
+        token::transfer(transfer_clawback_ctx, amount)?;
+        
+        // Update stake account
+        stake_account.rewards_claimed = stake_account.rewards_claimed.checked_sub(amount).unwrap();
+        stake_account.rewards_clawed_back = stake_account.rewards_clawed_back.checked_add(amount).unwrap();
+        
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
-        bump = staking_authority.bumps.staking_authority,
-    )]
-    pub staking_authority: Account<'info, StakingAuthority>,
+    // Move vault principal into the rewards pool during a shortfall, recorded as principal_debt.
+    // Requires admin and governance signatures, outstanding debt is capped at max_principal_borrow_bps of total_staked.
+    pub fn borrow_from_principal(ctx: Context<BorrowFromPrincipal>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return err!(StakingError::InvalidAmount);
+        }
+        
+        // Borrow only from the shared vault, held by the canonical authority
+        let staking_authority = &ctx.accounts.staking_authority;
+        staking_authority.check_shared_vault(&ctx.accounts.staking_vault.key())?;
+        check_vault_authority(staking_authority, &ctx.accounts.staking_vault)?;
+        
+        // Clamp outstanding debt to the configured fraction of principal
+        let principal_debt = staking_authority.principal_debt_after_borrow(amount)?;
+        
+        let staking_authority_seeds = &[
+            b"staking_authority".as_ref(),
+            staking_authority.token_mint.as_ref(),
+            &[staking_authority.bumps.staking_authority],
+        ];
+        let staking_authority_signer = &[&staking_authority_seeds[..]];
+        
+        let transfer_borrow_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.staking_vault.to_account_info(),
+                to: ctx.accounts.rewards_pool.to_account_info(),
+                authority: ctx.accounts.staking_authority.to_account_info(),
+            },
+            staking_authority_signer,
+        );
+        token::transfer(transfer_borrow_ctx, amount)?;
+        
+        // Update debt stats
+        ctx.accounts.staking_authority.principal_debt = principal_debt;
+        emit!(PrincipalBorrowed {
+            amount,
+            principal_debt,
+        });
+        
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
-        bump = stake_account.bump,
-        constraint = stake_account.owner == owner.key(),
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
+    // Add tokens to the rewards pool, repaying any principal debt to the vault first
+    pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return err!(StakingError::InvalidAmount);
+        }
+        
+        // Repayments only count when they land in the authority's shared vault
+        ctx.accounts.staking_authority.check_shared_vault(&ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        
+        let repaid = ctx.accounts.staking_authority.repay_principal(amount);
+        let funded = amount - repaid;
+        
+        // Repay borrowed principal
+        if repaid > 0 {
+            let transfer_repay_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.funder_token_account.to_account_info(),
+                    to: ctx.accounts.staking_vault.to_account_info(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                },
+            );
+            token::transfer(transfer_repay_ctx, repaid)?;
+            
+            emit!(PrincipalRepaid {
+                amount: repaid,
+                principal_debt: ctx.accounts.staking_authority.principal_debt,
+            });
+        }
+        
+        // Top up the rewards pool with the rest
+        if funded > 0 {
+            let transfer_fund_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.funder_token_account.to_account_info(),
+                    to: ctx.accounts.rewards_pool.to_account_info(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                },
+            );
+            token::transfer(transfer_fund_ctx, funded)?;
+        }
+        
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        constraint = token_account.owner == owner.key(),
-        constraint = token_account.mint == staking_authority.token_mint,
-    )]
-    pub token_account: Account<'info, TokenAccount>,
+    // Set the most principal that may be borrowed into the rewards pool, as a share of total_staked
+    pub fn set_max_principal_borrow(ctx: Context<AdminOnly>, max_principal_borrow_bps: u16) -> Result<()> {
+        if max_principal_borrow_bps > 10000 {
+            return err!(StakingError::InvalidPrincipalBorrowRatio);
+        }
+        ctx.accounts.staking_authority.max_principal_borrow_bps = max_principal_borrow_bps;
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        constraint = staking_vault.mint == staking_authority.token_mint,
-    )]
-    pub staking_vault: Account<'info, TokenAccount>,
+    // Slash slash_bps of a position's principal into the rewards pool. Settled rewards are
+    // cut by the same fraction when slash_includes_rewards is set, otherwise kept whole.
+    pub fn slash_position(ctx: Context<SlashPosition>, slash_bps: u16) -> Result<()> {
+        // A full slash would leave an active position with no principal, close it through unstake instead
+        if slash_bps == 0 || slash_bps >= 10000 {
+            return err!(StakingError::InvalidSlashAmount);
+        }
+
+        let clock = Clock::get()?;
+        
+        // Check if stake is active
+        let stake_account = &mut ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        ctx.accounts.staking_authority.check_tier_vault(stake_account.tier_index, &ctx.accounts.staking_vault.key())?;
+        check_vault_authority(&ctx.accounts.staking_authority, &ctx.accounts.staking_vault)?;
+        
+        let (slashed, rewards_slashed) = stake_account.slash(&mut ctx.accounts.staking_authority, slash_bps, &clock);
+        
+        let staking_authority_seeds = &[
+            b"staking_authority".as_ref(),
+            ctx.accounts.staking_authority.token_mint.as_ref(),
+            &[ctx.accounts.staking_authority.bumps.staking_authority],
+        ];
+        let staking_authority_signer = &[&staking_authority_seeds[..]];
+        
+        if slashed > 0 {
+            let transfer_slash_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.staking_vault.to_account_info(),
+                    to: ctx.accounts.rewards_pool.to_account_info(),
+                    authority: ctx.accounts.staking_authority.to_account_info(),
+                },
+                staking_authority_signer,
+            );
+            token::transfer(transfer_slash_ctx, slashed)?;
+        }
+        
+        emit!(PositionSlashed {
+            stake_account: stake_account.key(),
+            slash_bps,
+            principal_slashed: slashed,
+            rewards_slashed,
+        });
+        
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        constraint = rewards_pool.mint == staking_authority.token_mint,
-    )]
-    pub rewards_pool: Account<'info, TokenAccount>,
+    // Choose whether slashing also cuts a position's settled, unclaimed rewards
+    pub fn set_slash_includes_rewards(ctx: Context<AdminOnly>, slash_includes_rewards: bool) -> Result<()> {
+        ctx.accounts.staking_authority.slash_includes_rewards = slash_includes_rewards;
+        Ok(())
+    }
     
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-pub struct ClaimRewards<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    // Set the share of committed reward liability the rewards pool must keep on claims
+    pub fn set_reserve_ratio(ctx: Context<AdminOnly>, reserve_ratio_bps: u16) -> Result<()> {
+        if reserve_ratio_bps > 10000 {
+            return err!(StakingError::InvalidReserveRatio);
+        }
+        ctx.accounts.staking_authority.reserve_ratio_bps = reserve_ratio_bps;
+        Ok(())
+    }
     
-    #[account(
-        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
-        bump = staking_authority.bumps.staking_authority,
-    )]
-    pub staking_authority: Account<'info, StakingAuthority>,
+    // Flag the program as undercollateralized when the rewards pool holds less than
+    // reserve_ratio_bps of the committed liability, callable by any keeper
+    pub fn raise_solvency_alert(ctx: Context<SolvencyCheck>) -> Result<()> {
+        let clock = Clock::get()?;
+        let rewards_pool_balance = ctx.accounts.rewards_pool.amount;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if !staking_authority.is_undercollateralized(rewards_pool_balance) {
+            return err!(StakingError::SolvencyHealthy);
+        }
+        if staking_authority.undercollateralized {
+            return err!(StakingError::SolvencyAlertActive);
+        }
+        
+        staking_authority.undercollateralized = true;
+        staking_authority.undercollateralized_since = clock.unix_timestamp;
+        emit!(SolvencyAlertRaised {
+            rewards_pool_balance,
+            committed_liability: staking_authority.committed_liability,
+            reserve_ratio_bps: staking_authority.reserve_ratio_bps,
+        });
+        
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
-        bump = stake_account.bump,
-        constraint = stake_account.owner == owner.key(),
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
+    // Clear the undercollateralization flag once the rewards pool is back above the reserve, callable by any keeper
+    pub fn clear_solvency_alert(ctx: Context<SolvencyCheck>) -> Result<()> {
+        let rewards_pool_balance = ctx.accounts.rewards_pool.amount;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if !staking_authority.undercollateralized {
+            return err!(StakingError::NoSolvencyAlert);
+        }
+        if staking_authority.is_undercollateralized(rewards_pool_balance) {
+            return err!(StakingError::StillUndercollateralized);
+        }
+        
+        staking_authority.undercollateralized = false;
+        emit!(SolvencyAlertCleared {
+            rewards_pool_balance,
+            committed_liability: staking_authority.committed_liability,
+            undercollateralized_since: staking_authority.undercollateralized_since,
+        });
+        
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        constraint = token_account.owner == owner.key(),
-        constraint = token_account.mint == staking_authority.token_mint,
-    )]
-    pub token_account: Account<'info, TokenAccount>,
+    // Configure the LP program whose aged positions earn a staking reward boost
+    pub fn set_lp_boost(
+        ctx: Context<AdminOnly>,
+        lp_program: Pubkey,
+        lp_min_age_seconds: i64,
+        lp_boost_bps: u16,
+    ) -> Result<()> {
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.lp_program = lp_program;
+        staking_authority.lp_min_age_seconds = lp_min_age_seconds;
+        staking_authority.lp_boost_bps = lp_boost_bps;
+        Ok(())
+    }
     
-    #[account(
-        mut,
-        constraint = rewards_pool.mint == staking_authority.token_mint,
-    )]
-    pub rewards_pool: Account<'info, TokenAccount>,
+    // Commit the Merkle root of all active positions for light-client inclusion proofs
+    pub fn commit_positions_root(ctx: Context<AdminOnly>, root: [u8; 32], count: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.positions_root = root;
+        staking_authority.positions_root_count = count;
+        staking_authority.positions_root_committed_at = clock.unix_timestamp;
+        Ok(())
+    }
     
-    pub token_program: Program<'info, Token>,
-}
-
-#[account]
-pub struct StakingAuthority {
-    pub authority: Pubkey,           // 32
-    pub validator: Pubkey,           // 32
-    pub token_mint: Pubkey,          // 32
-    pub rewards_pool: Pubkey,        // 32
-    pub total_staked: u64,           // 8
-    pub staker_count: u64,           // 8
-    pub bumps: AuthorityBumps,       // 1
-}
+    // Check a position against the committed positions root, hashing its leaf from the position's fields
+    pub fn verify_inclusion(
+        ctx: Context<GetStakingStats>,
+        stake_account: Pubkey,
+        owner: Pubkey,
+        deposit_amount: u64,
+        unlock_time: i64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<bool> {
+        let staking_authority = &ctx.accounts.staking_authority;
+        if staking_authority.positions_root_committed_at == 0 {
+            return err!(StakingError::PositionsRootNotCommitted);
+        }
+        Ok(staking_authority.includes_position(&stake_account, &owner, deposit_amount, unlock_time, &proof))
+    }
+    
+    // Set the oracle allowed to report inflation
+    pub fn set_oracle(ctx: Context<AdminOnly>, oracle: Pubkey) -> Result<()> {
+        ctx.accounts.staking_authority.oracle = oracle;
+        Ok(())
+    }
+    
+    // Record the latest annual inflation rate reported by the oracle
+    pub fn report_inflation(ctx: Context<OracleReport>, inflation_bps: i16) -> Result<()> {
+        let clock = Clock::get()?;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.inflation_bps = inflation_bps;
+        staking_authority.inflation_updated_at = clock.unix_timestamp;
+        Ok(())
+    }
+    
+    // Record whether the referenced validator is jailed or delinquent. While suspension is enabled,
+    // jailed intervals are tracked and excluded from all reward accrual.
+    pub fn report_validator_status(ctx: Context<OracleReport>, jailed: bool) -> Result<()> {
+        let clock = Clock::get()?;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if jailed && staking_authority.jail_suspends_accrual {
+            staking_authority.start_jailed_interval(clock.unix_timestamp);
+        } else if !jailed {
+            staking_authority.end_jailed_interval(clock.unix_timestamp);
+        }
+        Ok(())
+    }
+    
+    // Toggle suspending reward accrual while the validator is reported jailed
+    pub fn set_jail_suspends_accrual(ctx: Context<AdminOnly>, enabled: bool) -> Result<()> {
+        let clock = Clock::get()?;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if !enabled {
+            staking_authority.end_jailed_interval(clock.unix_timestamp);
+        }
+        staking_authority.jail_suspends_accrual = enabled;
+        Ok(())
+    }
+    
+    // Pause or resume APY accrual for every position in one tier, other tiers keep accruing.
+    // Paused intervals are excluded from accrual, index emission is shared across tiers and unaffected.
+    pub fn set_tier_accrual_paused(ctx: Context<AdminOnly>, tier_index: u8, paused: bool) -> Result<()> {
+        if tier_index as usize >= TIER_COUNT {
+            return err!(StakingError::InvalidTier);
+        }
 
-#[account]
-pub struct StakeAccount {
-    pub owner: Pubkey,               // 32
-    pub stake_authority: Pubkey,     // 32
-    pub token_account: Pubkey,       // 32
-    pub deposit_amount: u64,         // 8
-    pub start_time: i64,             // 8
-    pub unlock_time: i64,            // 8
-    pub apy: u16,                    // 2 (stored as basis points, e.g., 500 = 5.00%)
-    pub rewards_claimed: u64,        // 8
-    pub last_claimed_time: i64,      // 8
-    pub is_active: bool,             // 1
-    pub bump: u8,                    // 1
-}
+        let clock = Clock::get()?;
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if paused {
+            staking_authority.pause_tier_accrual(tier_index, clock.unix_timestamp);
+        } else {
+            staking_authority.resume_tier_accrual(tier_index, clock.unix_timestamp);
+        }
+        Ok(())
+    }
+    
+    // Toggle accruing rewards at the real (inflation-adjusted) APY instead of the nominal one.
+    // The latest inflation figure applies to each position's unsettled accrual window.
+    pub fn set_real_yield_accrual(ctx: Context<AdminOnly>, enabled: bool) -> Result<()> {
+        ctx.accounts.staking_authority.real_yield_accrual = enabled;
+        Ok(())
+    }
+    
+    // Get a position's nominal and inflation-adjusted APY
+    pub fn get_real_apy(ctx: Context<CalculateRewards>) -> Result<RealApyResult> {
+        let staking_authority = &ctx.accounts.staking_authority;
+        let nominal_apy = ctx.accounts.stake_account.effective_apy();
+        
+        Ok(RealApyResult {
+            nominal_apy,
+            inflation_bps: staking_authority.inflation_bps,
+            real_apy: real_apy(nominal_apy, staking_authority.inflation_bps),
+            inflation_updated_at: staking_authority.inflation_updated_at,
+            real_yield_accrual: staking_authority.real_yield_accrual,
+        })
+    }
+    
+    // Set how long after staking a stake can be cancelled with a full refund, zero disables cancellation
+    pub fn set_cooling_off_period(ctx: Context<AdminOnly>, cooling_off_seconds: i64) -> Result<()> {
+        if cooling_off_seconds < 0 {
+            return err!(StakingError::InvalidCoolingOffPeriod);
+        }
+        ctx.accounts.staking_authority.cooling_off_seconds = cooling_off_seconds;
+        Ok(())
+    }
+    
+    // Grow an existing StakingAuthority to the current layout. New fields are appended and
+    // zero-initialized, which is their disabled default, except that accounts from before
+    // versioning get the defaults initialize sets. An un-migrated account fails to
+    // deserialize, so new fields are never read before this has run. Safe to call repeatedly.
+    // Authorities from before governance was required get `governance` here, afterwards it must
+    // match the current key, which only rotate_governance can change.
+    pub fn migrate_authority(ctx: Context<MigrateAuthority>, governance: Pubkey) -> Result<()> {
+        let account_info = ctx.accounts.staking_authority.to_account_info();
+        
+        // Check the account is a StakingAuthority owned by this program
+        if *account_info.owner != crate::ID {
+            return err!(StakingError::InvalidAuthorityAccount);
+        }
+        let (stored_authority, token_mint) = {
+            let data = account_info.try_borrow_data()?;
+            if data.len() < 8 + 32 + 32 + 32 || data[..8] != StakingAuthority::DISCRIMINATOR {
+                return err!(StakingError::InvalidAuthorityAccount);
+            }
+            // authority is the first field, token_mint the third
+            (
+                Pubkey::new_from_array(data[8..40].try_into().unwrap()),
+                Pubkey::new_from_array(data[72..104].try_into().unwrap()),
+            )
+        };
+        
+        // Check the canonical authority PDA and the signer
+        let (expected_address, _) =
+            Pubkey::find_program_address(&[b"staking_authority", token_mint.as_ref()], &crate::ID);
+        if account_info.key() != expected_address {
+            return err!(StakingError::InvalidAuthorityAccount);
+        }
+        if stored_authority != ctx.accounts.authority.key() {
+            return err!(StakingError::Unauthorized);
+        }
+        
+        // Grow the account and fund the extra rent
+        grow_account(
+            &account_info,
+            8 + StakingAuthority::SIZE,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program,
+        )?;
+        
+        // Stamp the current version
+        let mut staking_authority = StakingAuthority::try_deserialize(&mut &account_info.try_borrow_data()?[..])?;
+        if staking_authority.version < AUTHORITY_VERSION {
+            staking_authority.upgrade_layout();
+        }
+        
+        // Name the governance co-signer if the authority has none yet
+        if staking_authority.governance == Pubkey::default() {
+            staking_authority.assign_governance(governance)?;
+        } else if staking_authority.governance != governance {
+            return err!(StakingError::GovernanceAlreadySet);
+        }
+        staking_authority.try_serialize(&mut &mut account_info.try_borrow_mut_data()?[..])?;
+        
+        Ok(())
+    }
+    
+    // Suggest the shortest lock period, in days, whose tier APY earns target_reward on principal
+    pub fn suggest_lock_for_target(
+        ctx: Context<GetStakingStats>,
+        principal: u64,
+        target_reward: u64,
+    ) -> Result<u16> {
+        if principal == 0 {
+            return err!(StakingError::InvalidAmount);
+        }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
-pub struct AuthorityBumps {
-    pub staking_authority: u8,
-}
+        // Answer at the rates new positions get now, including any scheduled change already in force
+        let clock = Clock::get()?;
+        let staking_authority = &ctx.accounts.staking_authority;
+        let tier_apys: [u16; TIER_COUNT] =
+            core::array::from_fn(|tier_index| staking_authority.tier_apy(tier_index as u8, clock.unix_timestamp));
+        match lock_for_target(principal, target_reward, tier_apys, staking_authority.year_seconds()) {
+            Some(days) => Ok(days),
+            None => err!(StakingError::TargetRewardUnachievable),
+        }
+    }
+    
+    // Set the minimum time between keeper compounds of the same position
+    pub fn set_min_compound_interval(ctx: Context<AdminOnly>, min_compound_interval: i64) -> Result<()> {
+        ctx.accounts.staking_authority.min_compound_interval = min_compound_interval;
+        Ok(())
+    }
+    
+    // Set the minimum time between increase_stake calls on the same position
+    pub fn set_min_increase_interval(ctx: Context<AdminOnly>, min_increase_interval: i64) -> Result<()> {
+        ctx.accounts.staking_authority.min_increase_interval = min_increase_interval;
+        Ok(())
+    }
+    
+    // Set the most a single position can be paid in rewards per epoch, zero removes the cap.
+    // Unstake still pays everything owed, since nothing can carry past a closed position.
+    pub fn set_max_claim_per_epoch(ctx: Context<AdminOnly>, max_claim_per_epoch: u64) -> Result<()> {
+        ctx.accounts.staking_authority.max_claim_per_epoch = max_claim_per_epoch;
+        Ok(())
+    }
+    
+    // Schedule a new default-tier APY from effective_at, other tiers keep their spread over it.
+    // Only positions opened from then on are stamped with it, open positions keep their APY.
+    // Replaces any change still pending.
+    pub fn schedule_apy_change(ctx: Context<AdminOnly>, effective_at: i64, base_apy_bps: u16) -> Result<()> {
+        let clock = Clock::get()?;
+        if effective_at <= clock.unix_timestamp || base_apy_bps == 0 {
+            return err!(StakingError::InvalidApySchedule);
+        }
 
-// API result structs
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct ApyTier {
-    pub period_days: u16,
-    pub apy_bps: u16,
-}
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.base_apy_bps = staking_authority.base_apy_at(clock.unix_timestamp);
+        staking_authority.scheduled_apy_change = Some((effective_at, base_apy_bps));
+        Ok(())
+    }
+    
+    // Set the year basis APY accrues against (365, 360 or 365.25 days), only while no positions are open
+    // since settled reward units are converted with the basis in force when they are read
+    pub fn set_year_basis(ctx: Context<AdminOnly>, seconds_per_year: i64) -> Result<()> {
+        if ![SECONDS_PER_YEAR, YEAR_BASIS_360_SECONDS, YEAR_BASIS_365_25_SECONDS].contains(&seconds_per_year) {
+            return err!(StakingError::InvalidYearBasis);
+        }
 
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct StakingStatsResult {
-    pub total_staked: u64,
-    pub staker_count: u64,
-    pub validator: Pubkey,
-    pub apy_tiers: Vec<ApyTier>,
-}
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if staking_authority.staker_count > 0 {
+            return err!(StakingError::YearBasisLocked);
+        }
+        staking_authority.seconds_per_year = seconds_per_year;
+        Ok(())
+    }
+    
+    // Stop new positions from being opened, existing positions can still be increased, claimed and unstaked
+    pub fn set_new_stakes_frozen(ctx: Context<AdminOnly>, new_stakes_frozen: bool) -> Result<()> {
+        ctx.accounts.staking_authority.new_stakes_frozen = new_stakes_frozen;
+        Ok(())
+    }
+    
+    // Set how long an owner must be inactive before a beneficiary can claim, zero disables beneficiary claims
+    pub fn set_beneficiary_inactivity(ctx: Context<AdminOnly>, beneficiary_inactivity_seconds: i64) -> Result<()> {
+        if beneficiary_inactivity_seconds < 0 {
+            return err!(StakingError::InvalidInactivityThreshold);
+        }
+        ctx.accounts.staking_authority.beneficiary_inactivity_seconds = beneficiary_inactivity_seconds;
+        Ok(())
+    }
+    
+    // Set how far past now a lock may end, zero removes the limit
+    pub fn set_max_unlock_horizon(ctx: Context<AdminOnly>, max_unlock_horizon_seconds: i64) -> Result<()> {
+        if max_unlock_horizon_seconds < 0 {
+            return err!(StakingError::InvalidLockHorizon);
+        }
+        ctx.accounts.staking_authority.max_unlock_horizon_seconds = max_unlock_horizon_seconds;
+        Ok(())
+    }
+    
+    // Set the cooldown between request_unbond and unstake, zero lets unlocked stakes withdraw directly
+    pub fn set_unbonding_period(ctx: Context<AdminOnly>, unbonding_seconds: i64) -> Result<()> {
+        if unbonding_seconds < 0 {
+            return err!(StakingError::InvalidUnbondingPeriod);
+        }
+        ctx.accounts.staking_authority.unbonding_seconds = unbonding_seconds;
+        Ok(())
+    }
+    
+    // Set the one-time bonus, in basis points of accrued rewards, paid at unstake to positions
+    // that never claimed before unlock
+    pub fn set_hodler_bonus(ctx: Context<AdminOnly>, hodler_bonus_bps: u16) -> Result<()> {
+        ctx.accounts.staking_authority.hodler_bonus_bps = hodler_bonus_bps;
+        Ok(())
+    }
+    
+    // Set the smallest pending reward amount claim_rewards will pay out
+    pub fn set_min_claimable_rewards(ctx: Context<AdminOnly>, min_claimable_rewards: u64) -> Result<()> {
+        ctx.accounts.staking_authority.min_claimable_rewards = min_claimable_rewards;
+        Ok(())
+    }
+    
+    // Start accrual of new positions at the next epoch boundary, zero epoch length means the default
+    pub fn set_accrual_epoch(ctx: Context<AdminOnly>, align_accrual_to_epoch: bool, epoch_seconds: i64) -> Result<()> {
+        if epoch_seconds < 0 {
+            return err!(StakingError::InvalidEpochLength);
+        }
 
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct RewardsResult {
-    pub available_rewards: u64,
-    pub apy: u16,
-    pub time_staked: i64,
-    pub unlock_time: i64,
-    pub current_time: i64,
-}
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.align_accrual_to_epoch = align_accrual_to_epoch;
+        staking_authority.epoch_seconds = epoch_seconds;
+        Ok(())
+    }
+    
+    // Give a tier its own emission budget, zero removes it. APY accrual for the tier stops
+    // once the budget is paid out, rewards accrued before then stay claimable.
+    pub fn set_tier_emission_budget(ctx: Context<AdminOnly>, tier_index: u8, budget: u64) -> Result<()> {
+        if tier_index as usize >= TIER_COUNT {
+            return err!(StakingError::InvalidTier);
+        }
 
-// Account contexts for view methods
-#[derive(Accounts)]
-pub struct GetStakingStats<'info> {
-    #[account(
-        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
-        bump = staking_authority.bumps.staking_authority,
-    )]
-    pub staking_authority: Account<'info, StakingAuthority>,
-}
+        // Accrual has already been frozen for the tier
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if staking_authority.tier_budget_exhausted_at[tier_index as usize] > 0 {
+            return err!(StakingError::TierBudgetExhausted);
+        }
+        staking_authority.tier_emission_budgeted[tier_index as usize] = budget > 0;
+        staking_authority.tier_emission_remaining[tier_index as usize] = budget;
+        Ok(())
+    }
+    
+    // Designate the account that covers claims the rewards pool can't, with a lifetime hard cap
+    pub fn set_reward_backstop(ctx: Context<SetRewardBackstop>, backstop_cap: u64) -> Result<()> {
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        staking_authority.reward_backstop = ctx.accounts.reward_backstop.key();
+        staking_authority.backstop_cap = backstop_cap;
+        Ok(())
+    }
+    
+    // Grow positions passed as remaining accounts to the current layout, the admin paying the extra
+    // rent, and checkpoint them onto the current reward formula. Positions from the original program
+    // (formula version 0) carry their accrual since the last claim over, settled under the original
+    // formula. Positions opened before staked_mint existed are backfilled with the authority's mint.
+    pub fn batch_migrate_positions<'info>(ctx: Context<'_, '_, '_, 'info, MigratePositions<'info>>) -> Result<()> {
+        if ctx.remaining_accounts.len() > MAX_MIGRATION_BATCH {
+            return err!(StakingError::MigrationBatchTooLarge);
+        }
 
-#[derive(Accounts)]
-pub struct CalculateRewards<'info> {
-    pub owner: Signer<'info>,
+        let clock = Clock::get()?;
+        let staking_authority_key = ctx.accounts.staking_authority.key();
+        
+        for account_info in ctx.remaining_accounts.iter() {
+            // Check the account is one of this authority's positions before paying to grow it
+            if *account_info.owner != crate::ID {
+                return err!(StakingError::InvalidStakeAccount);
+            }
+            {
+                let data = account_info.try_borrow_data()?;
+                // stake_authority is the second field
+                if data.len() < 8 + 32 + 32
+                    || data[..8] != StakeAccount::DISCRIMINATOR
+                    || data[40..72] != staking_authority_key.to_bytes()
+                {
+                    return err!(StakingError::InvalidStakeAccount);
+                }
+            }
+            grow_account(
+                account_info,
+                8 + StakeAccount::SIZE,
+                &ctx.accounts.authority.to_account_info(),
+                &ctx.accounts.system_program,
+            )?;
+            
+            let mut stake_account: Account<StakeAccount> = Account::try_from(account_info)?;
+            if stake_account.formula_version == 0 {
+                stake_account.migrate_from_original(&mut ctx.accounts.staking_authority, &clock);
+            } else if stake_account.formula_version < REWARD_FORMULA_VERSION && stake_account.is_active {
+                stake_account.settle_pending(&mut ctx.accounts.staking_authority, &clock);
+            }
+            stake_account.formula_version = REWARD_FORMULA_VERSION;
+            if stake_account.staked_mint == Pubkey::default() {
+                stake_account.staked_mint = ctx.accounts.staking_authority.token_mint;
+            }
+            if stake_account.lock_duration_seconds == 0 {
+                stake_account.lock_duration_seconds = (stake_account.unlock_time - stake_account.start_time).max(0);
+            }
+            stake_account.exit(&crate::ID)?;
+        }
+        
+        Ok(())
+    }
     
-    #[account(
-        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
-        bump = staking_authority.bumps.staking_authority,
-    )]
-    pub staking_authority: Account<'info, StakingAuthority>,
+    // Register a dedicated vault for a tier, only while the tier holds no principal. The vault must
+    // be empty and not already serve another tier or the rewards pool, so it only ever holds this
+    // tier's principal and recover_tier_surplus never counts other principal as surplus.
+    pub fn set_tier_vault(ctx: Context<SetTierVault>, tier_index: u8) -> Result<()> {
+        if tier_index as usize >= TIER_COUNT {
+            return err!(StakingError::InvalidTier);
+        }
+
+        let staking_authority = &mut ctx.accounts.staking_authority;
+        if staking_authority.tier_staked[tier_index as usize] > 0 {
+            return err!(StakingError::TierHasPrincipal);
+        }
+        let tier_vault = &ctx.accounts.tier_vault;
+        if staking_authority.tier_of_vault(&tier_vault.key()).is_some()
+            || tier_vault.key() == staking_authority.rewards_pool
+            || tier_vault.amount > 0
+        {
+            return err!(StakingError::TierVaultInUse);
+        }
+        staking_authority.tier_vaults[tier_index as usize] = ctx.accounts.tier_vault.key();
+        
+        Ok(())
+    }
     
-    #[account(
-        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
-        bump = stake_account.bump,
-        constraint = stake_account.owner == owner.key(),
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
-}
+    // Recover tokens in a tier vault beyond the principal staked in that tier
+    pub fn recover_tier_surplus(ctx: Context<RecoverTierSurplus>, tier_index: u8, amount: u64) -> Result<()> {
+        if tier_index as usize >= TIER_COUNT {
+            return err!(StakingError::InvalidTier);
+        }
 
-#[error_code]
-pub enum StakingError {
-    #[msg("Invalid staking amount")]
-    InvalidAmount,
+        // Only registered tier vaults hold a single tier's principal
+        let staking_authority = &ctx.accounts.staking_authority;
+        let tier_vault = staking_authority.tier_vaults[tier_index as usize];
+        if tier_vault == Pubkey::default() || tier_vault != ctx.accounts.tier_vault.key() {
+            return err!(StakingError::InvalidTierVault);
+        }
+        
+        // Principal-backing balance is never touched
+        let surplus = ctx
+            .accounts
+            .tier_vault
+            .amount
+            .saturating_sub(staking_authority.tier_staked[tier_index as usize]);
+        if amount == 0 || amount > surplus {
+            return err!(StakingError::SurplusExceeded);
+        }
+        
+        let staking_authority_seeds = &[
+            b"staking_authority".as_ref(),
+            staking_authority.token_mint.as_ref(),
+            &[staking_authority.bumps.staking_authority],
+        ];
+        let staking_authority_signer = &[&staking_authority_seeds[..]];
+        
+        let transfer_surplus_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.tier_vault.to_account_info(),
+                to: ctx.accounts.destination.to_account_info(),
+                authority: ctx.accounts.staking_authority.to_account_info(),
+            },
+            staking_authority_signer,
+        );
+        token::transfer(transfer_surplus_ctx, amount)?;
+        
+        Ok(())
+    }
     
-    #[msg("Staking period has not ended yet")]
-    StakingPeriodNotEnded,
+    // Get a position's lottery weight and its deterministic tiebreak for an epoch.
+    // Draw order is weight descending, equal weights ordered by tiebreak ascending.
+    pub fn get_lottery_weight(ctx: Context<GetPosition>, epoch: u64) -> Result<LotteryWeightResult> {
+        let stake_account = &ctx.accounts.stake_account;
+        let weight = if stake_account.is_active { stake_account.effective_shares } else { 0 };
+        let epoch_seed = lottery_epoch_seed(&ctx.accounts.staking_authority.key(), epoch);
+        
+        Ok(LotteryWeightResult {
+            weight,
+            epoch,
+            epoch_seed,
+            tiebreak: lottery_tiebreak(&stake_account.key(), &epoch_seed),
+        })
+    }
     
-    #[msg("No rewards available for claiming")]
-    NoRewardsAvailable,
+    // Get a position's outstanding reward obligation: rewards accrued but not yet paid,
+    // plus accrual at its nominal APY from now until unlock. Summed over positions this is
+    // the program's total outstanding reward obligation.
+    pub fn get_position_liability(ctx: Context<GetPosition>) -> Result<PositionLiabilityResult> {
+        let clock = Clock::get()?;
+        Ok(ctx.accounts.stake_account.liability(&ctx.accounts.staking_authority, &clock))
+    }
     
-    #[msg("Stake is not active")]
-    InactiveStake,
-}
-
-// Size constants
-impl StakingAuthority {
-    pub const SIZE: usize = 32 + 32 + 32 + 32 + 8 + 8 + 1;
-}
+    // Get what emergency_unstake would cost right now: the penalty on principal plus the
+    // pending rewards it forfeits, all zero once the position has unlocked
+    pub fn get_early_exit_cost(ctx: Context<GetPosition>) -> Result<EarlyExitCostResult> {
+        let clock = Clock::get()?;
+        let staking_authority = &ctx.accounts.staking_authority;
+        let stake_account = &ctx.accounts.stake_account;
+        if !stake_account.is_active || clock.unix_timestamp >= stake_account.unlock_time {
+            return Ok(EarlyExitCostResult {
+                penalty: 0,
+                forfeited_rewards: 0,
+                total_cost: 0,
+                early_exit_allowed: false,
+            });
+        }
+        
+        let penalty = early_exit_penalty(stake_account.deposit_amount, staking_authority.early_exit_penalty_bps);
+        let forfeited_rewards = if staking_authority.rewards_claimable(clock.unix_timestamp) {
+            stake_account.pending_rewards(staking_authority, &clock)
+        } else {
+            0
+        };
+        
+        Ok(EarlyExitCostResult {
+            penalty,
+            forfeited_rewards,
+            total_cost: penalty.checked_add(forfeited_rewards).unwrap(),
+            early_exit_allowed: !APY_TIERS[stake_account.tier_index as usize].no_early_exit,
+        })
+    }
+    
+    // Get the position's recorded reward payouts with timestamps in [year_start, year_end),
+    // oldest first, for income reporting. Only the last CLAIM_HISTORY_LEN payouts are kept.
+    pub fn get_tax_records(ctx: Context<GetPosition>, year_start: i64, year_end: i64) -> Result<Vec<ClaimRecord>> {
+        if year_end <= year_start {
+            return err!(StakingError::InvalidDateRange);
+        }
 
-impl StakeAccount {
-    pub const SIZE: usize = 32 + 32 + 32 + 8 + 8 + 8 + 2 + 8 + 8 + 1 + 1;
-}
\ No newline at end of file
+        Ok(ctx
+            .accounts
+            .stake_account
+            .claim_records()
+            .filter(|record| record.timestamp >= year_start && record.timestamp < year_end)
+            .collect())
+    }
+    
+    // Get the APY a position has actually earned since it opened, in basis points:
+    // (claimed + pending rewards) / (deposit_amount * elapsed years). Boosts, index emission,
+    // slashing and tier changes all show up here, unlike the stored APY.
+    pub fn get_realized_apy(ctx: Context<GetPosition>) -> Result<RealizedApyResult> {
+        let clock = Clock::get()?;
+        Ok(ctx.accounts.stake_account.realized_apy(&ctx.accounts.staking_authority, &clock))
+    }
+    
+    // Preview what change_tier would do right now, without changing any state
+    pub fn preview_tier_change(ctx: Context<GetPosition>, new_tier_index: u8) -> Result<TierChangePreview> {
+        let clock = Clock::get()?;
+        let staking_authority = &ctx.accounts.staking_authority;
+        let stake_account = &ctx.accounts.stake_account;
+        stake_account.check_tier_change(new_tier_index)?;
+        
+        let new_unlock_time = stake_account.unlock_time_for_tier(new_tier_index, clock.unix_timestamp);
+        staking_authority.check_unlock_horizon(new_unlock_time, clock.unix_timestamp)?;
+        
+        Ok(TierChangePreview {
+            old_tier_index: stake_account.tier_index,
+            new_tier_index,
+            old_apy: stake_account.apy,
+            new_apy: staking_authority.tier_apy(new_tier_index, clock.unix_timestamp),
+            old_unlock_time: stake_account.unlock_time,
+            new_unlock_time,
+            settled_rewards: stake_account.pending_rewards(staking_authority, &clock),
+        })
+    }
+    
+    // Rewards pool balance as basis points of committed reward liability, u64::MAX while nothing is owed
+    pub fn get_collateralization_ratio(ctx: Context<GetCollateralization>) -> Result<u64> {
+        Ok(ctx.accounts.staking_authority.collateralization_bps(ctx.accounts.rewards_pool.amount))
+    }
+    
+    // Earliest upcoming boundary that changes what a position earns or can do, for client notifications
+    pub fn get_next_event(ctx: Context<GetPosition>) -> Result<NextEventResult> {
+        let clock = Clock::get()?;
+        let (timestamp, event) = ctx
+            .accounts
+            .stake_account
+            .next_event(&ctx.accounts.staking_authority, clock.unix_timestamp);
+        Ok(NextEventResult { timestamp, event })
+    }
+    
+    // Get staking stats
+    pub fn get_staking_stats(ctx: Context<GetStakingStats>) -> Result<StakingStatsResult> {
+        let clock = Clock::get()?;
+        let staking_authority = &ctx.accounts.staking_authority;
+        
+        // Return staking stats
+        Ok(StakingStatsResult {
+            total_staked: staking_authority.total_staked,
+            staker_count: staking_authority.staker_count,
+            positions_ever_opened: staking_authority.positions_ever_opened,
+            validator: staking_authority.validator,
+            apy_tiers: (0..TIER_COUNT)
+                .map(|tier_index| ApyTier {
+                    apy_bps: staking_authority.tier_apy(tier_index as u8, clock.unix_timestamp),
+                    ..APY_TIERS[tier_index]
+                })
+                .collect(),
+        })
+    }
+    
+    // Calculate available rewards for a stake account
+    pub fn calculate_rewards(ctx: Context<CalculateRewards>) -> Result<RewardsResult> {
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp;
+        
+        // Get stake account
+        let stake_account = &ctx.accounts.stake_account;
+        if !stake_account.is_active {
+            return Ok(RewardsResult {
+                available_rewards: 0,
+                apy: stake_account.apy,
+                time_staked: 0,
+                unlock_time: stake_account.unlock_time,
+                current_time
+            });
+        }
+        
+        // Calculate time staked
+        let time_staked = current_time - stake_account.last_claimed_time;
+        let rewards = if ctx.accounts.staking_authority.rewards_claimable(current_time) {
+            stake_account.pending_rewards(&ctx.accounts.staking_authority, &clock)
+        } else {
+            0
+        };
+        
+        // Return rewards result
+        Ok(RewardsResult {
+            available_rewards: rewards,
+            apy: stake_account.clamp_to_band(stake_account.apy),
+            time_staked,
+            unlock_time: stake_account.unlock_time,
+            current_time
+        })
+    }
+    
+    // Expose every input to the reward formula so a payout can be verified independently
+    pub fn get_reward_trace(ctx: Context<CalculateRewards>) -> Result<RewardTrace> {
+        let clock = Clock::get()?;
+        Ok(ctx.accounts.stake_account.reward_trace(&ctx.accounts.staking_authority, &clock))
+    }
+}
+
+// Grow a program account to `new_len`, `payer` funding the extra rent. No-op once large enough.
+pub fn grow_account<'info>(
+    account_info: &AccountInfo<'info>,
+    new_len: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    if account_info.data_len() >= new_len {
+        return Ok(());
+    }
+    let rent_due = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(account_info.lamports());
+    if rent_due > 0 {
+        let transfer_rent_ctx = CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.clone(),
+                to: account_info.clone(),
+            },
+        );
+        system_program::transfer(transfer_rent_ctx, rent_due)?;
+    }
+    account_info.realloc(new_len, true)?;
+    Ok(())
+}
+
+// Redundant with the account constraints: the authority must be the canonical PDA for its mint
+// and own the vault, and the vault must have no delegate that could move principal around us
+pub fn check_vault_authority(
+    staking_authority: &Account<StakingAuthority>,
+    staking_vault: &Account<TokenAccount>,
+) -> Result<()> {
+    staking_authority.check_vault_holder(&staking_authority.key(), staking_vault)
+}
+
+// Return an unlocked position's principal and rewards to `destination` and close it out
+pub fn unstake_position<'info>(
+    staking_authority: &mut Account<'info, StakingAuthority>,
+    stake_account: &mut Account<'info, StakeAccount>,
+    staking_vault: &Account<'info, TokenAccount>,
+    rewards_pool: &Account<'info, TokenAccount>,
+    destination: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let clock = Clock::get()?;
+    let current_time = clock.unix_timestamp;
+    
+    // Check if staking period has ended
+    if current_time < stake_account.unlock_time {
+        return err!(StakingError::StakingPeriodNotEnded);
+    }
+    
+    // Check if stake is active
+    if !stake_account.is_active {
+        return err!(StakingError::InactiveStake);
+    }
+    staking_authority.check_tier_vault(stake_account.tier_index, &staking_vault.key())?;
+    check_vault_authority(staking_authority, staking_vault)?;
+    
+    // With an unbonding period, withdrawal waits for the cooldown to end
+    stake_account.check_cooldown_ended(staking_authority, current_time)?;
+    
+    // Calculate rewards, forfeiting pre-cap rewards once the grace window has closed
+    stake_account.sync_reward_index(staking_authority, &clock);
+    stake_account.settle_before_cap(staking_authority, &clock);
+    let mut rewards = if staking_authority.rewards_claimable(current_time) {
+        stake_account.pending_rewards(staking_authority, &clock)
+    } else {
+        0
+    };
+    
+    // Top off rewards lost to rounding so the position receives the advertised APY, bounded by the pool
+    if staking_authority.guarantee_advertised_apy
+        && staking_authority.rewards_claimable(current_time)
+    {
+        let owed = stake_account
+            .entitled_rewards(staking_authority, &clock)
+            .saturating_sub(stake_account.rewards_claimed);
+        rewards = rewards.max(owed.min(rewards_pool.amount));
+    }
+    
+    // Positions never claimed from before unlock earn the hodler bonus, bounded by the pool
+    if stake_account.interim_claims == 0 && staking_authority.hodler_bonus_bps > 0 {
+        let bonus = (rewards as u128 * staking_authority.hodler_bonus_bps as u128 / 10000) as u64;
+        let bonus = bonus.min(rewards_pool.amount.saturating_sub(rewards));
+        stake_account.bonus_rewards_paid = stake_account.bonus_rewards_paid.checked_add(bonus).unwrap();
+        rewards += bonus;
+    }
+    
+    // Transfer principal back to user
+    let staking_authority_seeds = &[
+        b"staking_authority".as_ref(),
+        staking_authority.token_mint.as_ref(),
+        &[staking_authority.bumps.staking_authority],
+    ];
+    let staking_authority_signer = &[&staking_authority_seeds[..]];
+    
+    let transfer_principal_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        Transfer {
+            from: staking_vault.to_account_info(),
+            to: destination.to_account_info(),
+            authority: staking_authority.to_account_info(),
+        },
+        staking_authority_signer,
+    );
+    token::transfer(transfer_principal_ctx, stake_account.deposit_amount)?;
+    
+    // Transfer rewards if any
+    if rewards > 0 {
+        let transfer_rewards_ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: rewards_pool.to_account_info(),
+                to: destination.to_account_info(),
+                authority: staking_authority.to_account_info(),
+            },
+            staking_authority_signer,
+        );
+        token::transfer(transfer_rewards_ctx, rewards)?;
+    }
+    
+    // Update stake account
+    stake_account.is_active = false;
+    stake_account.sync_reward_index(staking_authority, &clock);
+    let released_liability = stake_account.reward_liability;
+    stake_account.reward_liability = 0;
+    stake_account.rewards_claimed = stake_account.rewards_claimed.checked_add(rewards).unwrap();
+    stake_account.record_claim(rewards, false, &clock);
+    
+    // Update staking stats
+    staking_authority.record_position_closed(stake_account.tier_index, stake_account.deposit_amount, released_liability);
+    staking_authority.record_emission(rewards, &clock);
+    staking_authority.record_tier_emission(stake_account.tier_index, rewards, &clock);
+    
+    Ok(())
+}
+
+// Move settled rewards from the rewards pool into the vault as additional principal
+pub fn restake_rewards<'info>(
+    staking_authority: &mut Account<'info, StakingAuthority>,
+    stake_account: &mut Account<'info, StakeAccount>,
+    rewards_pool: &Account<'info, TokenAccount>,
+    staking_vault: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+    clock: &Clock,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    if stake_account.unbonding {
+        return err!(StakingError::PositionUnbonding);
+    }
+    
+    let staking_authority_seeds = &[
+        b"staking_authority".as_ref(),
+        staking_authority.token_mint.as_ref(),
+        &[staking_authority.bumps.staking_authority],
+    ];
+    let staking_authority_signer = &[&staking_authority_seeds[..]];
+    
+    let transfer_rewards_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        Transfer {
+            from: rewards_pool.to_account_info(),
+            to: staking_vault.to_account_info(),
+            authority: staking_authority.to_account_info(),
+        },
+        staking_authority_signer,
+    );
+    token::transfer(transfer_rewards_ctx, amount)?;
+    
+    stake_account.compound_rewards(staking_authority, amount, clock);
+    
+    Ok(())
+}
+
+// Index of the highest tier whose minimum lock period is met, falling back to the default tier
+pub fn tier_for_lock_period(lock_period_days: u16) -> usize {
+    APY_TIERS
+        .iter()
+        .rposition(|tier| lock_period_days >= tier.period_days)
+        .unwrap_or(0)
+}
+
+// total_staked after a signed correction, None on underflow or overflow
+pub fn apply_total_staked_delta(total_staked: u64, delta: i64) -> Option<u64> {
+    if delta >= 0 {
+        total_staked.checked_add(delta as u64)
+    } else {
+        total_staked.checked_sub(delta.unsigned_abs())
+    }
+}
+
+// Shortest lock period reaching target_reward at the given per-tier APYs, trying tiers from
+// shortest to longest. Each tier applies from its own minimum period up to the next tier's.
+pub fn lock_for_target(
+    principal: u64,
+    target_reward: u64,
+    tier_apys: [u16; TIER_COUNT],
+    seconds_per_year: i64,
+) -> Option<u16> {
+    let reward_units = target_reward as u128 * 10000 * seconds_per_year as u128;
+    
+    for (index, tier) in APY_TIERS.iter().enumerate() {
+        let min_days = if index == 0 { 0 } else { tier.period_days as u128 };
+        let max_days = match APY_TIERS.get(index + 1) {
+            Some(next_tier) => next_tier.period_days as u128 - 1,
+            None => u16::MAX as u128,
+        };
+        
+        // Days needed at this tier's APY, rounded up
+        let units_per_day = principal as u128 * tier_apys[index] as u128 * 86400;
+        if units_per_day == 0 {
+            continue;
+        }
+        let needed_days = (reward_units + units_per_day - 1) / units_per_day;
+        let days = needed_days.max(min_days);
+        if days <= max_days {
+            return Some(days as u16);
+        }
+    }
+    
+    None
+}
+
+// Merkle leaf for a position: keccak(0x00 || stake account || owner || deposit_amount || unlock_time)
+pub fn position_leaf(stake_account_key: &Pubkey, owner: &Pubkey, deposit_amount: u64, unlock_time: i64) -> [u8; 32] {
+    keccak::hashv(&[
+        &[MERKLE_LEAF_PREFIX],
+        stake_account_key.as_ref(),
+        owner.as_ref(),
+        &deposit_amount.to_le_bytes(),
+        &unlock_time.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+// Seed for an epoch's lottery draw: keccak("lottery" || staking authority || epoch).
+// Derived only from public inputs so any draw can be reproduced off-chain, no randomness involved.
+pub fn lottery_epoch_seed(staking_authority_key: &Pubkey, epoch: u64) -> [u8; 32] {
+    keccak::hashv(&[b"lottery".as_ref(), staking_authority_key.as_ref(), &epoch.to_le_bytes()]).to_bytes()
+}
+
+// Tiebreak between equal-weight positions: keccak(stake account || epoch seed), lower sorts first.
+// Distinct per position since account keys are unique, and reshuffled every epoch by the seed.
+pub fn lottery_tiebreak(stake_account_key: &Pubkey, epoch_seed: &[u8; 32]) -> [u8; 32] {
+    keccak::hashv(&[stake_account_key.as_ref(), epoch_seed]).to_bytes()
+}
+
+// Internal Merkle node: keccak(0x01 || lower child || higher child), sorted so proofs need no direction bits
+pub fn merkle_node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (lower, higher) = if a <= b { (a, b) } else { (b, a) };
+    keccak::hashv(&[&[MERKLE_NODE_PREFIX], lower, higher]).to_bytes()
+}
+
+// Fold a proof into a root from a leaf built by position_leaf
+pub fn merkle_root_from_proof(leaf: [u8; 32], proof: &[[u8; 32]]) -> [u8; 32] {
+    proof.iter().fold(leaf, |node, sibling| merkle_node(&node, sibling))
+}
+
+// Inflation-adjusted APY in basis points
+pub fn real_apy(nominal_apy: u16, inflation_bps: i16) -> i32 {
+    nominal_apy as i32 - inflation_bps as i32
+}
+
+// Penalty charged on principal for an early exit
+pub fn early_exit_penalty(deposit_amount: u64, penalty_bps: u16) -> u64 {
+    (deposit_amount as u128 * penalty_bps as u128 / 10000) as u64
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakingAuthority::SIZE,
+        seeds = [b"staking_authority", Pubkey::from_str("4kXPBvQthvpes9TC7h6tXsYxWPUbYWpocBMVUG3eBLy4").unwrap().as_ref()],
+        bump,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    /// CHECK: This account is validated in the instruction
+    pub rewards_pool: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + StakeAccount::SIZE,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == owner.key(),
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClawbackRewards<'info> {
+    pub authority: Signer<'info>,
+    
+    pub governance: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+        has_one = governance,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    /// CHECK: Only used to derive the stake account address
+    pub owner: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == owner.key(),
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct BorrowFromPrincipal<'info> {
+    pub authority: Signer<'info>,
+    
+    pub governance: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+        has_one = governance,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetApyBand<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", stake_account.owner.as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SlashPosition<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", stake_account.owner.as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewards<'info> {
+    pub funder: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        constraint = funder_token_account.owner == funder.key(),
+        constraint = funder_token_account.mint == staking_authority.token_mint,
+    )]
+    pub funder_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+        constraint = staking_vault.owner == staking_authority.key() @ StakingError::InvalidVaultAuthority,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.key() == staking_authority.rewards_pool,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ReserveStake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + StakeReservation::SIZE,
+        seeds = [b"stake_reservation", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump,
+    )]
+    pub reservation: Account<'info, StakeReservation>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FulfillReservation<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"stake_reservation", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = reservation.bump,
+        constraint = reservation.owner == owner.key(),
+    )]
+    pub reservation: Account<'info, StakeReservation>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + StakeAccount::SIZE,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == owner.key(),
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseReservation<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"stake_reservation", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = reservation.bump,
+        constraint = reservation.owner == owner.key(),
+    )]
+    pub reservation: Account<'info, StakeReservation>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireReservation<'info> {
+    pub keeper: Signer<'info>,
+    
+    /// CHECK: Only receives the reservation's rent, checked against the reservation owner
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+    
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"stake_reservation", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = reservation.bump,
+        constraint = reservation.owner == owner.key(),
+    )]
+    pub reservation: Account<'info, StakeReservation>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyLpBoost<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Owned by the configured LP program, layout parsed with LpPositionRecord
+    #[account(
+        constraint = *lp_position.owner == staking_authority.lp_program @ StakingError::InvalidLpPosition,
+    )]
+    pub lp_position: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStake<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CrankCompound<'info> {
+    pub keeper: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", stake_account.owner.as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct IncreaseStake<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == owner.key(),
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendLock<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct RequestUnbond<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeTier<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    // Vault holding the principal under the current tier
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    // Vault for the new tier, the same account when neither tier has a dedicated vault
+    #[account(
+        mut,
+        constraint = new_staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub new_staking_vault: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == owner.key(),
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.key() == staking_authority.rewards_pool,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeTo<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    // Any token account of the staked mint, chosen by the owner
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct EmitAccrualCheckpoint<'info> {
+    pub keeper: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", stake_account.owner.as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverPosition<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    // Accepted by address, recover_position checks its authority and that it is not at its stored-bump PDA
+    #[account(mut)]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == stake_account.owner,
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRecentStake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == owner.key(),
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct BeneficiaryUnbond<'info> {
+    pub beneficiary: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", stake_account.owner.as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.beneficiary == Some(beneficiary.key()) @ StakingError::NotBeneficiary,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAsBeneficiary<'info> {
+    pub beneficiary: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", stake_account.owner.as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.beneficiary == Some(beneficiary.key()) @ StakingError::NotBeneficiary,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = beneficiary_token_account.owner == beneficiary.key(),
+        constraint = beneficiary_token_account.mint == staking_authority.token_mint,
+    )]
+    pub beneficiary_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    // The owner or the position's claim delegate
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+    
+    /// CHECK: Only used to derive the stake account address, checked against the stake owner
+    pub owner: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+        constraint = stake_account.is_claim_authority(&claimer.key()) @ StakingError::NotClaimAuthority,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == owner.key(),
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    /// CHECK: Only receives lamports, required and checked against the authority config while a claim fee is set
+    #[account(mut)]
+    pub fee_collector: Option<UncheckedAccount<'info>>,
+    
+    // Only needed when the pool may fall short, address checked against the authority config
+    #[account(
+        mut,
+        constraint = reward_backstop.key() == staking_authority.reward_backstop,
+    )]
+    pub reward_backstop: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAndCompound<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        constraint = token_account.owner == owner.key(),
+        constraint = token_account.mint == staking_authority.token_mint,
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = staking_vault.mint == staking_authority.token_mint,
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = rewards_pool.mint == staking_authority.token_mint,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+    
+    /// CHECK: Only receives lamports, required and checked against the authority config while a claim fee is set
+    #[account(mut)]
+    pub fee_collector: Option<UncheckedAccount<'info>>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct StakingAuthority {
+    pub authority: Pubkey,           // 32
+    pub validator: Pubkey,           // 32
+    pub token_mint: Pubkey,          // 32
+    pub rewards_pool: Pubkey,        // 32
+    pub total_staked: u64,           // 8
+    pub staker_count: u64,           // 8
+    pub bumps: AuthorityBumps,       // 1
+    pub early_exit_penalty_bps: u16, // 2
+    pub slots_per_year: u64,         // 8 (0 = timestamp-based accrual)
+    pub emission_cap: u64,           // 8 (0 = uncapped)
+    pub total_rewards_emitted: u64,  // 8
+    pub cap_reached_at: i64,         // 8 (0 = cap not reached)
+    pub cap_reached_slot: u64,       // 8
+    pub cap_grace_seconds: i64,      // 8
+    pub guarantee_advertised_apy: bool, // 1
+    pub claim_fee_lamports: u64,     // 8 (0 = no fee)
+    pub fee_collector: Pubkey,       // 32
+    pub governance: Pubkey,          // 32
+    pub reserve_ratio_bps: u16,      // 2 (0 = no reserve)
+    pub committed_liability: u64,    // 8 (rewards promised to active positions through unlock, plus distributed index emission)
+    pub lp_program: Pubkey,          // 32
+    pub lp_min_age_seconds: i64,     // 8
+    pub lp_boost_bps: u16,           // 2 (0 = disabled)
+    pub positions_root: [u8; 32],    // 32
+    pub positions_root_count: u64,   // 8
+    pub positions_root_committed_at: i64, // 8
+    pub oracle: Pubkey,              // 32
+    pub inflation_bps: i16,          // 2
+    pub inflation_updated_at: i64,   // 8
+    pub real_yield_accrual: bool,    // 1
+    pub cooling_off_seconds: i64,    // 8
+    pub reward_rate_per_second: u64, // 8 (index emission, 0 = off)
+    pub acc_reward_per_share: u128,  // 16 (scaled by REWARD_INDEX_PRECISION)
+    pub index_updated_at: i64,       // 8
+    pub total_effective_shares: u128, // 16
+    pub version: u8,                 // 1 (see AUTHORITY_VERSION)
+    pub min_compound_interval: i64,  // 8 (since v3)
+    pub min_claimable_rewards: u64,  // 8 (since v4)
+    pub tier_vaults: [Pubkey; TIER_COUNT], // 32 * TIER_COUNT (since v5, default = shared vault)
+    pub tier_staked: [u64; TIER_COUNT], // 8 * TIER_COUNT (since v5)
+    pub align_accrual_to_epoch: bool, // 1 (since v6)
+    pub epoch_seconds: i64,          // 8 (since v6, 0 = DEFAULT_EPOCH_SECONDS)
+    pub positions_ever_opened: u64,  // 8 (since v7, never decremented)
+    pub reward_backstop: Pubkey,     // 32 (since v8)
+    pub backstop_cap: u64,           // 8 (since v8, 0 = disabled)
+    pub backstop_used: u64,          // 8 (since v8)
+    pub new_stakes_frozen: bool,     // 1 (since v9)
+    pub seconds_per_year: i64,       // 8 (since v10, 0 = SECONDS_PER_YEAR)
+    pub beneficiary_inactivity_seconds: i64, // 8 (since v11, 0 = beneficiary claims disabled)
+    pub principal_debt: u64,         // 8 (since v12, principal borrowed into the rewards pool)
+    pub max_principal_borrow_bps: u16, // 2 (since v12, 0 = borrowing disabled)
+    pub slash_includes_rewards: bool, // 1 (since v13)
+    pub auction_max_bonus_bps: u16,  // 2 (since v14)
+    pub auction_duration_seconds: i64, // 8 (since v14, 0 = no auction)
+    pub max_unlock_horizon_seconds: i64, // 8 (since v15, 0 = unlimited)
+    pub jail_suspends_accrual: bool, // 1 (since v16)
+    pub validator_jailed: bool,      // 1 (since v16, open jailed interval)
+    pub jailed_since: i64,           // 8 (since v16)
+    pub total_jailed_seconds: i64,   // 8 (since v16, closed jailed intervals)
+    pub index_jailed_seconds: i64,   // 8 (since v16, jailed seconds at index_updated_at)
+    pub tier_emission_budgeted: [bool; TIER_COUNT], // 1 * TIER_COUNT (since v17)
+    pub tier_emission_remaining: [u64; TIER_COUNT], // 8 * TIER_COUNT (since v17)
+    pub tier_budget_exhausted_at: [i64; TIER_COUNT], // 8 * TIER_COUNT (since v17, 0 = not exhausted)
+    pub tier_budget_exhausted_slot: [u64; TIER_COUNT], // 8 * TIER_COUNT (since v17)
+    pub undercollateralized: bool,   // 1 (since v18, set by raise_solvency_alert)
+    pub undercollateralized_since: i64, // 8 (since v18)
+    pub unbonding_seconds: i64,      // 8 (since v19, 0 = no cooldown)
+    pub hodler_bonus_bps: u16,       // 2 (since v20, 0 = disabled)
+    pub min_increase_interval: i64,  // 8 (since v21, 0 = no limit)
+    pub max_claim_per_epoch: u64,    // 8 (since v22, 0 = no cap)
+    pub tier_accrual_paused: [bool; TIER_COUNT], // 1 * TIER_COUNT (since v23, open paused interval)
+    pub tier_paused_since: [i64; TIER_COUNT], // 8 * TIER_COUNT (since v23)
+    pub tier_total_paused_seconds: [i64; TIER_COUNT], // 8 * TIER_COUNT (since v23, closed paused intervals)
+    pub base_apy_bps: u16,           // 2 (since v24, default tier APY in force, 0 = APY_TIERS)
+    pub scheduled_apy_change: Option<(i64, u16)>, // 1 + 8 + 2 (since v24, effective time and new base APY)
+}
+
+#[account]
+pub struct StakeAccount {
+    pub owner: Pubkey,               // 32
+    pub stake_authority: Pubkey,     // 32
+    pub token_account: Pubkey,       // 32
+    pub deposit_amount: u64,         // 8
+    pub start_time: i64,             // 8
+    pub unlock_time: i64,            // 8
+    pub apy: u16,                    // 2 (stored as basis points, e.g., 500 = 5.00%)
+    pub rewards_claimed: u64,        // 8
+    pub last_claimed_time: i64,      // 8
+    pub is_active: bool,             // 1
+    pub bump: u8,                    // 1
+    pub tier_index: u8,              // 1 (index into APY_TIERS)
+    pub principal_seconds: u128,     // 16 (principal * seconds accrued since last claim)
+    pub accrual_updated_at: i64,     // 8
+    pub start_slot: u64,             // 8
+    pub accrual_updated_slot: u64,   // 8
+    pub accrued_before_cap: u64,     // 8
+    pub lifetime_reward_units: u128, // 16 (sum of principal-seconds * apy over closed windows)
+    pub rewards_clawed_back: u64,    // 8
+    pub accrued_unclaimed: u64,      // 8
+    pub reward_liability: u64,       // 8 (share of committed_liability)
+    pub boost_bps: u16,              // 2 (added to apy)
+    pub effective_shares: u128,      // 16 (deposit_amount * tier weight_multiplier / 10000)
+    pub reward_debt: u128,           // 16
+    pub index_rewards_earned: u64,   // 8
+    pub auto_compound: bool,         // 1
+    pub last_compounded_at: i64,     // 8
+    pub formula_version: u8,         // 1 (see REWARD_FORMULA_VERSION)
+    pub last_accrual_rate: u64,      // 8 (tokens per day at the current APY, informational)
+    pub beneficiary: Option<Pubkey>, // 1 + 32
+    pub owner_last_active: i64,      // 8 (last owner-signed action)
+    pub min_apy: u16,                // 2 (negotiated floor, 0 = none)
+    pub max_apy: u16,                // 2 (negotiated ceiling, 0 = none)
+    pub jailed_seconds_checkpoint: i64, // 8 (authority jailed seconds at the last accrual update)
+    pub claim_history: [ClaimRecord; CLAIM_HISTORY_LEN], // ClaimRecord::SIZE * CLAIM_HISTORY_LEN (ring buffer)
+    pub claim_history_count: u64,    // 8 (payouts ever recorded, next slot is count % CLAIM_HISTORY_LEN)
+    pub unbonding: bool,             // 1 (no accrual while set)
+    pub cooldown_end: i64,           // 8 (earliest withdrawal while unbonding)
+    pub interim_claims: u32,         // 4 (claims made before unlock, any forfeits the hodler bonus)
+    pub bonus_rewards_paid: u64,     // 8 (hodler bonus paid, counts as entitled)
+    pub staked_mint: Pubkey,         // 32 (mint of the principal, also a stake account seed)
+    pub claim_delegate: Option<Pubkey>, // 1 + 32 (may claim to the owner, never unstake)
+    pub last_increased_at: i64,      // 8
+    pub claim_epoch: i64,            // 8 (epoch claimed_this_epoch belongs to)
+    pub claimed_this_epoch: u64,     // 8
+    pub tier_paused_seconds_checkpoint: i64, // 8 (tier paused seconds at the last accrual update)
+    pub last_checkpoint_at: i64,     // 8 (last AccrualCheckpoint event)
+    pub auto_relock_on_claim: bool,  // 1
+    pub lock_duration_seconds: i64,  // 8 (lock chosen at open, backfilled by batch_migrate_positions)
+}
+
+#[account]
+pub struct StakeReservation {
+    pub owner: Pubkey,               // 32
+    pub stake_authority: Pubkey,     // 32
+    pub amount: u64,                 // 8
+    pub lock_period_days: u16,       // 2
+    pub apy: u16,                    // 2 (fixed at reservation time)
+    pub created_at: i64,             // 8
+    pub valid_until: i64,            // 8
+    pub bump: u8,                    // 1
+    pub auction_bonus_bps: u16,      // 2 (bonus at created_at, decaying to zero)
+    pub auction_duration_seconds: i64, // 8 (0 = no auction)
+}
+
+// Leading fields of a position account in the external LP program:
+// 8-byte discriminator, owner (32), opened_at (8)
+pub struct LpPositionRecord {
+    pub owner: Pubkey,
+    pub opened_at: i64,
+}
+
+impl LpPositionRecord {
+    pub const LEN: usize = 8 + 32 + 8;
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < Self::LEN {
+            return err!(StakingError::InvalidLpPosition);
+        }
+        Ok(Self {
+            owner: Pubkey::new_from_array(data[8..40].try_into().unwrap()),
+            opened_at: i64::from_le_bytes(data[40..48].try_into().unwrap()),
+        })
+    }
+}
+
+// One reward payout, either paid to the owner or restaked as principal
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct ClaimRecord {
+    pub timestamp: i64,
+    pub amount: u64,
+    pub compounded: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct AuthorityBumps {
+    pub staking_authority: u8,
+}
+
+// API result structs
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct ApyTier {
+    pub period_days: u16,
+    pub apy_bps: u16,
+    pub no_early_exit: bool,
+    pub weight_multiplier: u16, // basis points, 10000 = 1x share of index emission
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct StakingStatsResult {
+    pub total_staked: u64,
+    pub staker_count: u64,
+    pub positions_ever_opened: u64,
+    pub validator: Pubkey,
+    pub apy_tiers: Vec<ApyTier>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RewardsResult {
+    pub available_rewards: u64,
+    pub apy: u16,
+    pub time_staked: i64,
+    pub unlock_time: i64,
+    pub current_time: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LotteryWeightResult {
+    pub weight: u128,      // tier-weighted effective shares, 0 once inactive
+    pub epoch: u64,
+    pub epoch_seed: [u8; 32],
+    pub tiebreak: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct PositionLiabilityResult {
+    pub accrued_unpaid: u64,
+    pub future_accrual: u64,
+    pub total_liability: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct EarlyExitCostResult {
+    pub penalty: u64,
+    pub forfeited_rewards: u64,
+    pub total_cost: u64,
+    pub early_exit_allowed: bool, // false past unlock or for no-early-exit tiers
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RealizedApyResult {
+    pub total_rewards_earned: u64,
+    pub elapsed_seconds: i64,
+    pub realized_apy_bps: u64, // 0 once inactive
+}
+
+// timestamp is 0 when event is None
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct NextEventResult {
+    pub timestamp: i64,
+    pub event: NextEventKind,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum NextEventKind {
+    None,
+    AccrualStart,  // epoch-aligned accrual window opens
+    CoolingOffEnd, // cancel_recent_stake no longer allowed
+    ClaimCapReset, // epoch rollover restores max_claim_per_epoch
+    Unlock,
+    CooldownEnd,   // unbonded principal can be withdrawn
+}
+
+// settled_rewards is what change_tier settles at the old tier, staying claimable afterwards
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct TierChangePreview {
+    pub old_tier_index: u8,
+    pub new_tier_index: u8,
+    pub old_apy: u16,
+    pub new_apy: u16,
+    pub old_unlock_time: i64,
+    pub new_unlock_time: i64,
+    pub settled_rewards: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RealApyResult {
+    pub nominal_apy: u16,
+    pub inflation_bps: i16,
+    pub real_apy: i32,
+    pub inflation_updated_at: i64,
+    pub real_yield_accrual: bool,
+}
+
+// Reward formula inputs: available_rewards = accrued_before_cap
+//     + accrued_unclaimed + (principal_seconds + deposit_amount * accrual_elapsed) * accrual_apy / (10000 * seconds_per_year)
+//     + effective_shares * acc_reward_per_share / REWARD_INDEX_PRECISION - reward_debt
+// where accrual_apy = apy + boost_bps, less inflation_bps when real_yield_accrual is on,
+// kept within [min_apy, max_apy] (a zero max_apy is no ceiling)
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RewardTrace {
+    pub deposit_amount: u64,
+    pub apy: u16,
+    pub boost_bps: u16,
+    pub inflation_bps: i16,
+    pub real_yield_accrual: bool,
+    pub min_apy: u16,
+    pub max_apy: u16,
+    pub accrual_apy: u16,
+    pub start_time: i64,
+    pub last_claimed_time: i64,
+    pub accrual_updated_at: i64,
+    pub accrual_updated_slot: u64,
+    pub current_time: i64,
+    pub current_slot: u64,
+    pub slots_per_year: u64,
+    pub seconds_per_year: i64,
+    pub accrual_elapsed: u64,
+    pub principal_seconds: u128,
+    pub accrued_before_cap: u64,
+    pub accrued_unclaimed: u64,
+    pub effective_shares: u128,
+    pub reward_debt: u128,
+    pub acc_reward_per_share: u128,
+    pub rewards_claimed: u64,
+    pub cap_reached_at: i64,
+    pub cap_reached_slot: u64,
+    pub rewards_claimable: bool,
+    pub available_rewards: u64,
+}
+
+// Events
+#[event]
+pub struct TotalStakedSynced {
+    pub delta: i64,
+    pub old_total_staked: u64,
+    pub new_total_staked: u64,
+}
+
+#[event]
+pub struct GovernanceRotated {
+    pub old_governance: Pubkey,
+    pub new_governance: Pubkey,
+}
+
+#[event]
+pub struct RewardRateUpdated {
+    pub old_reward_per_second: u64,
+    pub new_reward_per_second: u64,
+    pub acc_reward_per_share: u128,
+}
+
+#[event]
+pub struct ReserveFloorReached {
+    pub stake_account: Pubkey,
+    pub requested: u64,
+    pub paid: u64,
+    pub rewards_pool_balance: u64,
+    pub committed_liability: u64,
+}
+
+#[event]
+pub struct RewardBackstopUsed {
+    pub stake_account: Pubkey,
+    pub amount: u64,
+    pub backstop_used: u64,
+    pub backstop_cap: u64,
+}
+
+#[event]
+pub struct ReservationExpired {
+    pub reservation: Pubkey,
+    pub owner: Pubkey,
+    pub valid_until: i64,
+}
+
+#[event]
+pub struct BeneficiaryClaimed {
+    pub stake_account: Pubkey,
+    pub owner: Pubkey,
+    pub beneficiary: Pubkey,
+    pub principal: u64,
+    pub rewards: u64,
+}
+
+#[event]
+pub struct SolvencyAlertRaised {
+    pub rewards_pool_balance: u64,
+    pub committed_liability: u64,
+    pub reserve_ratio_bps: u16,
+}
+
+#[event]
+pub struct SolvencyAlertCleared {
+    pub rewards_pool_balance: u64,
+    pub committed_liability: u64,
+    pub undercollateralized_since: i64,
+}
+
+#[event]
+pub struct PositionSlashed {
+    pub stake_account: Pubkey,
+    pub slash_bps: u16,
+    pub principal_slashed: u64,
+    pub rewards_slashed: u64,
+}
+
+#[event]
+pub struct PrincipalBorrowed {
+    pub amount: u64,
+    pub principal_debt: u64,
+}
+
+#[event]
+pub struct PrincipalRepaid {
+    pub amount: u64,
+    pub principal_debt: u64,
+}
+
+#[event]
+pub struct AccrualCheckpoint {
+    pub stake_account: Pubkey,
+    pub owner: Pubkey,
+    pub deposit_amount: u64,
+    pub accrued: u64,          // pending rewards as of timestamp
+    pub entitled_rewards: u64, // lifetime earned, claimed or not
+    pub rewards_claimed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PositionRecovered {
+    pub stake_account: Pubkey,
+    pub owner: Pubkey,
+    pub principal: u64,
+}
+
+#[event]
+pub struct PositionBumpRestored {
+    pub stake_account: Pubkey,
+    pub owner: Pubkey,
+    pub bump: u8,
+}
+
+#[event]
+pub struct Compounded {
+    pub stake_account: Pubkey,
+    pub amount: u64,
+    pub deposit_amount: u64,
+}
+
+// Account context for authority-only methods
+#[derive(Accounts)]
+pub struct AdminOnly<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+}
+
+#[derive(Accounts)]
+pub struct GovernanceOnly<'info> {
+    pub governance: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = governance,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+}
+
+#[derive(Accounts)]
+pub struct SetTierVault<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        constraint = tier_vault.mint == staking_authority.token_mint,
+        constraint = tier_vault.owner == staking_authority.key(),
+    )]
+    pub tier_vault: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverTierSurplus<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(mut)]
+    pub tier_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = destination.mint == staking_authority.token_mint,
+    )]
+    pub destination: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetRewardBackstop<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        constraint = reward_backstop.mint == staking_authority.token_mint,
+        constraint = reward_backstop.owner == staking_authority.key()
+            || reward_backstop.delegate == COption::Some(staking_authority.key()),
+    )]
+    pub reward_backstop: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SolvencyCheck<'info> {
+    pub keeper: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    // Must be the configured pool, since anyone can call this
+    #[account(
+        constraint = rewards_pool.key() == staking_authority.rewards_pool,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+}
+
+// Account context for oracle reports
+#[derive(Accounts)]
+pub struct OracleReport<'info> {
+    pub oracle: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = oracle,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAuthority<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// CHECK: May still have an older layout, so it is validated in the instruction
+    #[account(mut)]
+    pub staking_authority: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePositions<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+        has_one = authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+// Account contexts for view methods
+#[derive(Accounts)]
+pub struct GetStakingStats<'info> {
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+}
+
+#[derive(Accounts)]
+pub struct GetCollateralization<'info> {
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        constraint = rewards_pool.key() == staking_authority.rewards_pool,
+    )]
+    pub rewards_pool: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct GetPosition<'info> {
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        seeds = [b"stake_account", stake_account.owner.as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CalculateRewards<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        seeds = [b"staking_authority", staking_authority.token_mint.as_ref()],
+        bump = staking_authority.bumps.staking_authority,
+    )]
+    pub staking_authority: Account<'info, StakingAuthority>,
+    
+    #[account(
+        seeds = [b"stake_account", owner.key().as_ref(), staking_authority.token_mint.as_ref()],
+        bump = stake_account.bump,
+        constraint = stake_account.owner == owner.key(),
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[error_code]
+pub enum StakingError {
+    #[msg("Invalid staking amount")]
+    InvalidAmount,
+    
+    #[msg("Staking period has not ended yet")]
+    StakingPeriodNotEnded,
+    
+    #[msg("No rewards available for claiming")]
+    NoRewardsAvailable,
+    
+    #[msg("Stake is not active")]
+    InactiveStake,
+    
+    #[msg("Staking period has already ended, use unstake")]
+    StakingPeriodEnded,
+    
+    #[msg("Early exit is not allowed for this tier")]
+    EarlyExitForbidden,
+    
+    #[msg("Total staked adjustment is out of bounds")]
+    TotalStakedOutOfBounds,
+    
+    #[msg("Emission cap has already been reached")]
+    EmissionCapReached,
+    
+    #[msg("Grace window must not be negative")]
+    InvalidGraceWindow,
+    
+    #[msg("Grace window for claiming pre-cap rewards has expired")]
+    EmissionGraceExpired,
+    
+    #[msg("Reservation expiry must be in the future")]
+    InvalidReservationExpiry,
+    
+    #[msg("Reservation has expired")]
+    ReservationExpired,
+    
+    #[msg("Claim fee is set but the configured fee collector was not provided")]
+    InvalidFeeCollector,
+    
+    #[msg("Governance has already been set, only the current governance can rotate it")]
+    GovernanceAlreadySet,
+    
+    #[msg("Clawback exceeds the over-distributed rewards")]
+    ClawbackExceedsOverDistribution,
+    
+    #[msg("Clawback has not been approved by the token account owner")]
+    ClawbackNotApproved,
+    
+    #[msg("Governance must be a key other than the default and the admin")]
+    InvalidGovernance,
+    
+    #[msg("Reserve ratio must not exceed 10000 basis points")]
+    InvalidReserveRatio,
+    
+    #[msg("LP boost is not enabled")]
+    LpBoostDisabled,
+    
+    #[msg("Invalid LP position account")]
+    InvalidLpPosition,
+    
+    #[msg("LP position has not been held long enough")]
+    LpPositionTooNew,
+    
+    #[msg("No positions root has been committed")]
+    PositionsRootNotCommitted,
+    
+    #[msg("Cooling-off period must not be negative")]
+    InvalidCoolingOffPeriod,
+    
+    #[msg("Cooling-off window has expired")]
+    CoolingOffExpired,
+    
+    #[msg("Account is not a valid staking authority")]
+    InvalidAuthorityAccount,
+    
+    #[msg("Signer is not the staking authority")]
+    Unauthorized,
+    
+    #[msg("Target reward cannot be reached within the maximum lock period")]
+    TargetRewardUnachievable,
+    
+    #[msg("Auto-compound is not enabled for this stake")]
+    AutoCompoundDisabled,
+    
+    #[msg("Stake was compounded too recently")]
+    CompoundTooSoon,
+    
+    #[msg("Pending rewards are below the minimum claim")]
+    BelowMinimumClaim,
+    
+    #[msg("Too many positions in one migration batch")]
+    MigrationBatchTooLarge,
+    
+    #[msg("Stake account does not belong to this staking authority")]
+    InvalidStakeAccount,
+    
+    #[msg("Invalid tier index")]
+    InvalidTier,
+    
+    #[msg("Tier still holds staked principal")]
+    TierHasPrincipal,
+    
+    #[msg("Vault is not the registered vault for this tier")]
+    InvalidTierVault,
+    
+    #[msg("Vault already holds tokens or serves another tier or the rewards pool")]
+    TierVaultInUse,
+    
+    #[msg("Amount exceeds the vault surplus")]
+    SurplusExceeded,
+    
+    #[msg("Epoch length cannot be negative")]
+    InvalidEpochLength,
+    
+    #[msg("New stakes are frozen")]
+    NewStakesFrozen,
+    
+    #[msg("Year basis must be 365, 360 or 365.25 days")]
+    InvalidYearBasis,
+    
+    #[msg("Year basis cannot change while positions are open")]
+    YearBasisLocked,
+    
+    #[msg("Claim share must not exceed 10000 basis points")]
+    InvalidClaimSplit,
+    
+    #[msg("Inactivity threshold must not be negative")]
+    InvalidInactivityThreshold,
+    
+    #[msg("Beneficiary claims are not enabled")]
+    BeneficiaryClaimsDisabled,
+    
+    #[msg("Signer is not the position's beneficiary")]
+    NotBeneficiary,
+    
+    #[msg("Owner has been active within the inactivity threshold")]
+    OwnerStillActive,
+    
+    #[msg("Principal borrow ratio must not exceed 10000 basis points")]
+    InvalidPrincipalBorrowRatio,
+    
+    #[msg("Borrowing would exceed the principal borrow limit")]
+    PrincipalBorrowExceeded,
+    
+    #[msg("Slash must be between 1 and 9999 basis points")]
+    InvalidSlashAmount,
+    
+    #[msg("Auction duration must not be negative")]
+    InvalidAuctionDuration,
+    
+    #[msg("Lock horizon must not be negative")]
+    InvalidLockHorizon,
+    
+    #[msg("Unlock time is beyond the maximum lock horizon")]
+    LockHorizonExceeded,
+    
+    #[msg("New unlock time must be later than the current one")]
+    InvalidLockExtension,
+    
+    #[msg("Reservation has not expired yet")]
+    ReservationNotExpired,
+    
+    #[msg("APY floor must not exceed the ceiling")]
+    InvalidApyBand,
+    
+    #[msg("Tier emission budget has already been exhausted")]
+    TierBudgetExhausted,
+    
+    #[msg("Positions can only move to a longer lock tier")]
+    InvalidTierChange,
+    
+    #[msg("Date range end must be after its start")]
+    InvalidDateRange,
+    
+    #[msg("Destination must be an unfrozen token account of the staked mint")]
+    InvalidDestination,
+    
+    #[msg("Rewards pool meets the reserve ratio")]
+    SolvencyHealthy,
+    
+    #[msg("Solvency alert is already raised")]
+    SolvencyAlertActive,
+    
+    #[msg("No solvency alert is raised")]
+    NoSolvencyAlert,
+    
+    #[msg("Rewards pool is still below the reserve ratio")]
+    StillUndercollateralized,
+    
+    #[msg("Position is unbonding")]
+    PositionUnbonding,
+    
+    #[msg("Unbonding is not enabled")]
+    UnbondingDisabled,
+    
+    #[msg("Unbonding period must not be negative")]
+    InvalidUnbondingPeriod,
+    
+    #[msg("Unbonding cooldown has not ended")]
+    CooldownNotEnded,
+    
+    #[msg("Signer is neither the owner nor the claim delegate")]
+    NotClaimAuthority,
+    
+    #[msg("Stake was increased too recently")]
+    IncreaseTooSoon,
+    
+    #[msg("Position has reached its reward claim cap for this epoch")]
+    EpochClaimCapReached,
+    
+    #[msg("Vault is not held by the canonical staking authority or has a delegate")]
+    InvalidVaultAuthority,
+    
+    #[msg("Position is reachable through its stored bump, use unstake")]
+    PositionNotRecoverable,
+    
+    #[msg("Scheduled APY change must take effect in the future with a non-zero APY")]
+    InvalidApySchedule,
+    
+    #[msg("Accrual checkpoint was emitted too recently")]
+    CheckpointTooSoon,
+}
+
+// Size constants
+impl StakingAuthority {
+    pub const SIZE: usize = 32 + 32 + 32 + 32 + 8 + 8 + 1 + 2 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 32 + 32 + 2 + 8 + 32 + 8 + 2 + 32 + 8 + 8 + 32 + 2 + 8 + 1 + 8 + 8 + 16 + 8 + 16 + 1 + 8 + 8
+        + 32 * TIER_COUNT + 8 * TIER_COUNT + 1 + 8 + 8 + 32 + 8 + 8 + 1 + 8 + 8 + 8 + 2 + 1 + 2 + 8 + 8 + 1 + 1 + 8 + 8 + 8
+        + 1 * TIER_COUNT + 8 * TIER_COUNT + 8 * TIER_COUNT + 8 * TIER_COUNT + 1 + 8 + 8 + 2 + 8 + 8
+        + 1 * TIER_COUNT + 8 * TIER_COUNT + 8 * TIER_COUNT + 2 + 11;
+}
+
+impl StakeAccount {
+    pub const SIZE: usize = 32 + 32 + 32 + 8 + 8 + 8 + 2 + 8 + 8 + 1 + 1 + 1 + 16 + 8 + 8 + 8 + 8 + 16 + 8 + 8 + 8 + 2 + 16 + 16 + 8 + 1 + 8 + 1 + 8 + 33 + 8 + 2 + 2 + 8
+        + ClaimRecord::SIZE * CLAIM_HISTORY_LEN + 8 + 1 + 8 + 4 + 8 + 32 + 33 + 8 + 8 + 8 + 8 + 8 + 1 + 8;
+}
+
+impl ClaimRecord {
+    pub const SIZE: usize = 8 + 8 + 1;
+}
+
+impl StakeReservation {
+    pub const SIZE: usize = 32 + 32 + 8 + 2 + 2 + 8 + 8 + 1 + 2 + 8;
+}
+
+impl StakeReservation {
+    // Reservations can be fulfilled up to and including valid_until, and expired after it
+    pub fn is_expired(&self, current_time: i64) -> bool {
+        current_time > self.valid_until
+    }
+
+    // APY locked by fulfilling at `current_time`: the reserved APY plus the auction bonus
+    // still left, which shrinks linearly from created_at until the auction ends
+    pub fn apy_at(&self, current_time: i64) -> u16 {
+        if self.auction_duration_seconds <= 0 {
+            return self.apy;
+        }
+        
+        let elapsed = (current_time - self.created_at).clamp(0, self.auction_duration_seconds);
+        let remaining = (self.auction_duration_seconds - elapsed) as u128;
+        let bonus = self.auction_bonus_bps as u128 * remaining / self.auction_duration_seconds as u128;
+        self.apy.saturating_add(bonus as u16)
+    }
+}
+
+// Layout versioning
+impl StakingAuthority {
+    // Give fields appended since the account's version initialize's values and stamp the current
+    // version. Accounts from before versioning get every non-zero default initialize sets.
+    pub fn upgrade_layout(&mut self) {
+        if self.version == 0 {
+            self.early_exit_penalty_bps = DEFAULT_EARLY_EXIT_PENALTY_BPS;
+            self.slots_per_year = DEFAULT_SLOTS_PER_YEAR;
+            self.cooling_off_seconds = DEFAULT_COOLING_OFF_SECONDS;
+        }
+        self.version = AUTHORITY_VERSION;
+    }
+}
+
+// Emission accounting
+impl StakingAuthority {
+    pub fn emission_cap_reached(&self) -> bool {
+        self.cap_reached_at > 0
+    }
+
+    // Rewards stay claimable until the grace window after the cap closes
+    pub fn rewards_claimable(&self, current_time: i64) -> bool {
+        !self.emission_cap_reached() || current_time <= self.cap_reached_at + self.cap_grace_seconds
+    }
+
+    // Governance co-signs dual-authorized actions, so it must be a real key distinct from the admin
+    pub fn assign_governance(&mut self, governance: Pubkey) -> Result<()> {
+        if governance == Pubkey::default() || governance == self.authority {
+            return err!(StakingError::InvalidGovernance);
+        }
+        self.governance = governance;
+        Ok(())
+    }
+
+    // Whether the committed positions root includes a position with these fields
+    pub fn includes_position(
+        &self,
+        stake_account_key: &Pubkey,
+        owner: &Pubkey,
+        deposit_amount: u64,
+        unlock_time: i64,
+        proof: &[[u8; 32]],
+    ) -> bool {
+        let leaf = position_leaf(stake_account_key, owner, deposit_amount, unlock_time);
+        merkle_root_from_proof(leaf, proof) == self.positions_root
+    }
+
+    // Claims below min_claimable_rewards wait until more rewards accumulate
+    pub fn check_min_claim(&self, amount: u64) -> Result<()> {
+        if amount < self.min_claimable_rewards {
+            return err!(StakingError::BelowMinimumClaim);
+        }
+        Ok(())
+    }
+
+    // Largest payout up to `amount` that keeps the rewards pool at or above
+    // reserve_ratio_bps of the committed liability remaining after the payout
+    pub fn max_payout_within_reserve(&self, pool_balance: u64, position_liability: u64, amount: u64) -> u64 {
+        let keeps_reserve = |payout: u64| {
+            if payout > pool_balance {
+                return false;
+            }
+            let liability_after = self.committed_liability.saturating_sub(payout.min(position_liability));
+            (pool_balance - payout) as u128 * 10000 >= self.reserve_ratio_bps as u128 * liability_after as u128
+        };
+        
+        if self.reserve_ratio_bps == 0 || keeps_reserve(amount) {
+            return amount;
+        }
+        
+        // The pool surplus over the floor only shrinks as the payout grows, so binary search the limit
+        let (mut low, mut high) = (0u64, amount);
+        while low < high {
+            let mid = low + (high - low + 1) / 2;
+            if keeps_reserve(mid) {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        if keeps_reserve(low) {
+            low
+        } else {
+            0
+        }
+    }
+
+    // `address` must be this authority's canonical PDA, holding the vault without a delegate
+    pub fn check_vault_holder(&self, address: &Pubkey, staking_vault: &TokenAccount) -> Result<()> {
+        let (expected_address, expected_bump) =
+            Pubkey::find_program_address(&[b"staking_authority", self.token_mint.as_ref()], &crate::ID);
+        if *address != expected_address
+            || self.bumps.staking_authority != expected_bump
+            || staking_vault.owner != expected_address
+            || staking_vault.delegate.is_some()
+        {
+            return err!(StakingError::InvalidVaultAuthority);
+        }
+        Ok(())
+    }
+
+    // unstake_to may pay out to any token account of the staked mint that can receive tokens
+    pub fn check_unstake_destination(&self, destination: &TokenAccount) -> Result<()> {
+        if destination.mint != self.token_mint || destination.is_frozen() {
+            return err!(StakingError::InvalidDestination);
+        }
+        Ok(())
+    }
+
+    // Principal debt is borrowed from and repaid to the shared vault, never a tier's dedicated vault
+    pub fn check_shared_vault(&self, staking_vault: &Pubkey) -> Result<()> {
+        if self.tier_of_vault(staking_vault).is_some() {
+            return err!(StakingError::InvalidTierVault);
+        }
+        Ok(())
+    }
+
+    // Tier a vault is registered to as its dedicated vault, if any
+    pub fn tier_of_vault(&self, vault: &Pubkey) -> Option<usize> {
+        self.tier_vaults
+            .iter()
+            .position(|tier_vault| *tier_vault != Pubkey::default() && tier_vault == vault)
+    }
+
+    // Whether the rewards pool holds less than reserve_ratio_bps of the committed liability
+    pub fn is_undercollateralized(&self, pool_balance: u64) -> bool {
+        (pool_balance as u128) * 10000 < self.committed_liability as u128 * self.reserve_ratio_bps as u128
+    }
+
+    // Default tier APY in force at `current_time`, taking over a scheduled change once it is due
+    pub fn base_apy_at(&self, current_time: i64) -> u16 {
+        match self.scheduled_apy_change {
+            Some((effective_at, base_apy_bps)) if current_time >= effective_at => base_apy_bps,
+            _ if self.base_apy_bps > 0 => self.base_apy_bps,
+            _ => APY_TIERS[0].apy_bps,
+        }
+    }
+
+    // APY new positions in a tier are stamped with: the tier's spread over the default tier on top of the base
+    pub fn tier_apy(&self, tier_index: u8, current_time: i64) -> u16 {
+        let spread = APY_TIERS[tier_index as usize].apy_bps - APY_TIERS[0].apy_bps;
+        self.base_apy_at(current_time).saturating_add(spread)
+    }
+
+    // Pool balance over committed liability in basis points, saturating when no liability is committed
+    pub fn collateralization_bps(&self, pool_balance: u64) -> u64 {
+        if self.committed_liability == 0 {
+            return u64::MAX;
+        }
+        (pool_balance as u128 * 10000 / self.committed_liability as u128).min(u64::MAX as u128) as u64
+    }
+
+    // Length of the configured year basis in seconds
+    pub fn year_seconds(&self) -> i64 {
+        if self.seconds_per_year > 0 {
+            self.seconds_per_year
+        } else {
+            SECONDS_PER_YEAR
+        }
+    }
+
+    // Most principal_debt allowed against the current total_staked
+    pub fn max_principal_borrow(&self) -> u64 {
+        (self.total_staked as u128 * self.max_principal_borrow_bps as u128 / 10000) as u64
+    }
+
+    // Outstanding principal_debt once `amount` more is borrowed, within max_principal_borrow
+    pub fn principal_debt_after_borrow(&self, amount: u64) -> Result<u64> {
+        let principal_debt = self.principal_debt.checked_add(amount).unwrap();
+        if principal_debt > self.max_principal_borrow() {
+            return err!(StakingError::PrincipalBorrowExceeded);
+        }
+        Ok(principal_debt)
+    }
+
+    // Apply a funding of `amount` to principal_debt first, returning the part that repaid debt
+    pub fn repay_principal(&mut self, amount: u64) -> u64 {
+        let repaid = amount.min(self.principal_debt);
+        self.principal_debt -= repaid;
+        repaid
+    }
+
+    // Backstop funds still available under the hard cap
+    pub fn backstop_remaining(&self) -> u64 {
+        self.backstop_cap.saturating_sub(self.backstop_used)
+    }
+
+    pub fn epoch_length(&self) -> i64 {
+        if self.epoch_seconds > 0 { self.epoch_seconds } else { DEFAULT_EPOCH_SECONDS }
+    }
+
+    // Epoch containing `current_time`, epochs counted from the unix epoch
+    pub fn current_epoch(&self, current_time: i64) -> i64 {
+        current_time.div_euclid(self.epoch_length())
+    }
+
+    // First epoch boundary at or after `current_time`, epochs counted from the unix epoch
+    pub fn next_epoch_start(&self, current_time: i64) -> i64 {
+        let epoch_seconds = self.epoch_length();
+        current_time.div_euclid(epoch_seconds) * epoch_seconds
+            + if current_time.rem_euclid(epoch_seconds) == 0 { 0 } else { epoch_seconds }
+    }
+
+    // Locks may not end further than max_unlock_horizon_seconds past now
+    pub fn check_unlock_horizon(&self, unlock_time: i64, current_time: i64) -> Result<()> {
+        if self.max_unlock_horizon_seconds > 0 && unlock_time - current_time > self.max_unlock_horizon_seconds {
+            return err!(StakingError::LockHorizonExceeded);
+        }
+        Ok(())
+    }
+
+    // Principal must move through the tier's registered vault if it has one, and otherwise
+    // through a vault no other tier has registered
+    pub fn check_tier_vault(&self, tier_index: u8, staking_vault: &Pubkey) -> Result<()> {
+        let tier_vault = self.tier_vaults[tier_index as usize];
+        let valid = if tier_vault != Pubkey::default() {
+            tier_vault == *staking_vault
+        } else {
+            self.tier_of_vault(staking_vault).is_none()
+        };
+        if !valid {
+            return err!(StakingError::InvalidTierVault);
+        }
+        Ok(())
+    }
+
+    // Only opening a position is gated by the freeze, increases, claims and unstakes go through
+    pub fn check_new_stakes_open(&self) -> Result<()> {
+        if self.new_stakes_frozen {
+            return err!(StakingError::NewStakesFrozen);
+        }
+        Ok(())
+    }
+
+    // Count a newly opened position and the liability it commits
+    pub fn record_position_opened(&mut self, tier_index: u8, amount: u64, reward_liability: u64) {
+        self.total_staked = self.total_staked.checked_add(amount).unwrap();
+        self.staker_count = self.staker_count.checked_add(1).unwrap();
+        self.positions_ever_opened = self.positions_ever_opened.checked_add(1).unwrap();
+        self.add_tier_stake(tier_index, amount);
+        self.committed_liability = self.committed_liability.checked_add(reward_liability).unwrap();
+    }
+
+    // Remove a closed position from the live stats, positions_ever_opened keeps counting it
+    pub fn record_position_closed(&mut self, tier_index: u8, amount: u64, released_liability: u64) {
+        self.total_staked = self.total_staked.checked_sub(amount).unwrap();
+        self.staker_count = self.staker_count.checked_sub(1).unwrap();
+        self.remove_tier_stake(tier_index, amount);
+        self.committed_liability = self.committed_liability.saturating_sub(released_liability);
+    }
+
+    pub fn add_tier_stake(&mut self, tier_index: u8, amount: u64) {
+        let tier_staked = &mut self.tier_staked[tier_index as usize];
+        *tier_staked = tier_staked.checked_add(amount).unwrap();
+    }
+
+    pub fn remove_tier_stake(&mut self, tier_index: u8, amount: u64) {
+        let tier_staked = &mut self.tier_staked[tier_index as usize];
+        *tier_staked = tier_staked.checked_sub(amount).unwrap();
+    }
+
+    // Seconds the validator has spent jailed with accrual suspended, up to `current_time`
+    pub fn jailed_seconds_at(&self, current_time: i64) -> i64 {
+        if self.validator_jailed {
+            self.total_jailed_seconds + (current_time - self.jailed_since).max(0)
+        } else {
+            self.total_jailed_seconds
+        }
+    }
+
+    pub fn start_jailed_interval(&mut self, current_time: i64) {
+        if !self.validator_jailed {
+            self.validator_jailed = true;
+            self.jailed_since = current_time;
+        }
+    }
+
+    pub fn end_jailed_interval(&mut self, current_time: i64) {
+        if self.validator_jailed {
+            self.total_jailed_seconds = self.jailed_seconds_at(current_time);
+            self.validator_jailed = false;
+        }
+    }
+
+    // Seconds the tier has spent with accrual paused, up to `current_time`
+    pub fn tier_paused_seconds_at(&self, tier_index: u8, current_time: i64) -> i64 {
+        let tier = tier_index as usize;
+        if self.tier_accrual_paused[tier] {
+            self.tier_total_paused_seconds[tier] + (current_time - self.tier_paused_since[tier]).max(0)
+        } else {
+            self.tier_total_paused_seconds[tier]
+        }
+    }
+
+    pub fn pause_tier_accrual(&mut self, tier_index: u8, current_time: i64) {
+        let tier = tier_index as usize;
+        if !self.tier_accrual_paused[tier] {
+            self.tier_accrual_paused[tier] = true;
+            self.tier_paused_since[tier] = current_time;
+        }
+    }
+
+    pub fn resume_tier_accrual(&mut self, tier_index: u8, current_time: i64) {
+        let tier = tier_index as usize;
+        if self.tier_accrual_paused[tier] {
+            self.tier_total_paused_seconds[tier] = self.tier_paused_seconds_at(tier_index, current_time);
+            self.tier_accrual_paused[tier] = false;
+        }
+    }
+
+    // Reward index as of `current_time`: emission per second shared across effective shares,
+    // stopping at the emission cap and skipping jailed time
+    pub fn reward_index_at(&self, current_time: i64) -> u128 {
+        let end_time = if self.emission_cap_reached() {
+            current_time.min(self.cap_reached_at)
+        } else {
+            current_time
+        };
+        let jailed = (self.jailed_seconds_at(end_time) - self.index_jailed_seconds).max(0);
+        let elapsed = (end_time - self.index_updated_at - jailed).max(0) as u128;
+        if elapsed == 0 || self.total_effective_shares == 0 || self.reward_rate_per_second == 0 {
+            return self.acc_reward_per_share;
+        }
+        
+        let emitted = self.reward_rate_per_second as u128 * elapsed;
+        self.acc_reward_per_share + emitted * REWARD_INDEX_PRECISION / self.total_effective_shares
+    }
+
+    // Switch the index emission rate at `current_time`, settling the index at the old rate first
+    // so rewards already accrued are not repriced. Returns the old rate.
+    pub fn change_reward_rate(&mut self, reward_per_second: u64, current_time: i64) -> u64 {
+        self.update_reward_index(current_time);
+        let old_reward_per_second = self.reward_rate_per_second;
+        self.reward_rate_per_second = reward_per_second;
+        old_reward_per_second
+    }
+
+    // Bring the stored reward index up to `current_time`, committing the emission it distributes
+    // as liability so the reserve checks cover index rewards like APY accrual
+    pub fn update_reward_index(&mut self, current_time: i64) {
+        let acc_reward_per_share = self.reward_index_at(current_time);
+        let emitted = (acc_reward_per_share - self.acc_reward_per_share) * self.total_effective_shares / REWARD_INDEX_PRECISION;
+        self.committed_liability = self.committed_liability.checked_add(emitted as u64).unwrap();
+        self.acc_reward_per_share = acc_reward_per_share;
+        if current_time >= self.index_updated_at {
+            self.index_updated_at = current_time;
+            self.index_jailed_seconds = self.jailed_seconds_at(current_time);
+        }
+    }
+
+    // Count paid rewards against the tier's budget, freezing the tier's accrual once it runs out
+    pub fn record_tier_emission(&mut self, tier_index: u8, amount: u64, clock: &Clock) {
+        let tier = tier_index as usize;
+        if !self.tier_emission_budgeted[tier] || self.tier_budget_exhausted_at[tier] > 0 {
+            return;
+        }
+        self.tier_emission_remaining[tier] = self.tier_emission_remaining[tier].saturating_sub(amount);
+        if self.tier_emission_remaining[tier] == 0 {
+            self.tier_budget_exhausted_at[tier] = clock.unix_timestamp;
+            self.tier_budget_exhausted_slot[tier] = clock.slot;
+        }
+    }
+
+    // Count paid rewards towards the cap, freezing accrual once it is hit
+    pub fn record_emission(&mut self, amount: u64, clock: &Clock) {
+        self.total_rewards_emitted = self.total_rewards_emitted.checked_add(amount).unwrap();
+        if self.emission_cap > 0
+            && !self.emission_cap_reached()
+            && self.total_rewards_emitted >= self.emission_cap
+        {
+            self.cap_reached_at = clock.unix_timestamp;
+            self.cap_reached_slot = clock.slot;
+        }
+    }
+
+    // SOL fee owed on a claim paying `paid`, claims that paid nothing are free
+    pub fn claim_fee_due(&self, paid: u64) -> u64 {
+        if paid > 0 {
+            self.claim_fee_lamports
+        } else {
+            0
+        }
+    }
+
+    // The fee collector is only required while a claim fee is set, and must then be the configured one
+    pub fn check_fee_collector(&self, fee_collector: Option<Pubkey>) -> Result<()> {
+        if self.claim_fee_lamports > 0 && fee_collector != Some(self.fee_collector) {
+            return err!(StakingError::InvalidFeeCollector);
+        }
+        Ok(())
+    }
+}
+
+// Reward accounting
+impl StakeAccount {
+    // Start a new position at the given APY
+    pub fn open(&mut self, amount: u64, lock_period_days: u16, apy: u16, seconds_per_year: i64, clock: &Clock) {
+        let current_time = clock.unix_timestamp;
+        
+        // Calculate unlock time based on lock period
+        let lock_period_seconds = (lock_period_days as i64) * 86400; // 86400 seconds = 1 day
+        
+        self.deposit_amount = amount;
+        self.start_time = current_time;
+        self.unlock_time = current_time + lock_period_seconds;
+        self.lock_duration_seconds = lock_period_seconds;
+        self.apy = apy;
+        self.tier_index = tier_for_lock_period(lock_period_days) as u8;
+        self.rewards_claimed = 0;
+        self.last_claimed_time = current_time;
+        self.principal_seconds = 0;
+        self.accrual_updated_at = current_time;
+        self.start_slot = clock.slot;
+        self.accrual_updated_slot = clock.slot;
+        self.accrued_unclaimed = 0;
+        self.reward_liability = 0;
+        self.boost_bps = 0;
+        self.effective_shares = 0;
+        self.reward_debt = 0;
+        self.index_rewards_earned = 0;
+        self.auto_compound = false;
+        self.auto_relock_on_claim = false;
+        self.last_compounded_at = current_time;
+        self.last_increased_at = current_time;
+        self.formula_version = REWARD_FORMULA_VERSION;
+        self.beneficiary = None;
+        self.claim_delegate = None;
+        self.owner_last_active = current_time;
+        self.min_apy = 0;
+        self.max_apy = 0;
+        self.unbonding = false;
+        self.cooldown_end = 0;
+        self.interim_claims = 0;
+        self.bonus_rewards_paid = 0;
+        self.claim_epoch = 0;
+        self.claimed_this_epoch = 0;
+        self.is_active = true;
+        self.commit_liability(amount, current_time, seconds_per_year);
+    }
+
+    // Positions from the original program accrued deposit * apy over the time since the last claim
+    // and stored nothing else. Carry that window over as the current accrual window, count what was
+    // already paid as earned, and commit the position's liability, tier principal and index shares.
+    pub fn migrate_from_original(&mut self, staking_authority: &mut StakingAuthority, clock: &Clock) {
+        let current_time = clock.unix_timestamp;
+        let lock_seconds = (self.unlock_time - self.start_time).max(0);
+        let seconds_per_year = staking_authority.year_seconds();
+        
+        self.tier_index = tier_for_lock_period((lock_seconds / 86400).min(u16::MAX as i64) as u16) as u8;
+        self.lock_duration_seconds = lock_seconds;
+        self.lifetime_reward_units = self.rewards_claimed as u128 * 10000 * seconds_per_year as u128;
+        self.owner_last_active = current_time;
+        self.last_compounded_at = current_time;
+        self.jailed_seconds_checkpoint = staking_authority.jailed_seconds_at(current_time);
+        self.tier_paused_seconds_checkpoint = staking_authority.tier_paused_seconds_at(self.tier_index, current_time);
+        if !self.is_active {
+            return;
+        }
+        
+        // Resume accrual from the last claim, estimating the slots that have passed since
+        let slots_since = |time: i64| {
+            if staking_authority.slots_per_year == 0 {
+                return 0;
+            }
+            ((current_time - time).max(0) as u128 * staking_authority.slots_per_year as u128 / seconds_per_year as u128) as u64
+        };
+        self.accrual_updated_at = self.last_claimed_time;
+        self.accrual_updated_slot = clock.slot.saturating_sub(slots_since(self.last_claimed_time));
+        self.start_slot = clock.slot.saturating_sub(slots_since(self.start_time));
+        
+        let liability = self.commit_liability(self.deposit_amount, current_time, seconds_per_year);
+        staking_authority.committed_liability = staking_authority.committed_liability.checked_add(liability).unwrap();
+        staking_authority.add_tier_stake(self.tier_index, self.deposit_amount);
+        self.sync_reward_index(staking_authority, clock);
+    }
+
+    // Defer the accrual window of a freshly opened position to the next epoch boundary when configured.
+    // Also anchors the jailed and paused-time checkpoints, so suspensions before the position opened are not held against it.
+    pub fn align_accrual_start(&mut self, staking_authority: &StakingAuthority, clock: &Clock) {
+        self.jailed_seconds_checkpoint = staking_authority.jailed_seconds_at(clock.unix_timestamp);
+        self.tier_paused_seconds_checkpoint = staking_authority.tier_paused_seconds_at(self.tier_index, clock.unix_timestamp);
+        if !staking_authority.align_accrual_to_epoch {
+            return;
+        }
+
+        let accrual_start = staking_authority.next_epoch_start(clock.unix_timestamp);
+        let delay_seconds = (accrual_start - clock.unix_timestamp) as u128;
+        self.accrual_updated_at = accrual_start;
+        if staking_authority.slots_per_year > 0 {
+            let delay_slots = delay_seconds * staking_authority.slots_per_year as u128 / staking_authority.year_seconds() as u128;
+            self.accrual_updated_slot = clock.slot.checked_add(delay_slots as u64).unwrap();
+        }
+    }
+
+    // Only active, non-unbonding positions can move, and only to a longer tier
+    pub fn check_tier_change(&self, new_tier_index: u8) -> Result<()> {
+        if new_tier_index as usize >= TIER_COUNT {
+            return err!(StakingError::InvalidTier);
+        }
+        if !self.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        if self.unbonding {
+            return err!(StakingError::PositionUnbonding);
+        }
+        if new_tier_index <= self.tier_index {
+            return err!(StakingError::InvalidTierChange);
+        }
+        Ok(())
+    }
+
+    // The owner or its claim delegate may claim, only the owner may unstake
+    pub fn is_claim_authority(&self, signer: &Pubkey) -> bool {
+        *signer == self.owner || self.claim_delegate == Some(*signer)
+    }
+
+    // Unlock time after moving to a tier: the new tier's minimum lock from now, never earlier than today's
+    pub fn unlock_time_for_tier(&self, tier_index: u8, current_time: i64) -> i64 {
+        self.unlock_time
+            .max(current_time + APY_TIERS[tier_index as usize].period_days as i64 * 86400)
+    }
+
+    // APY the position currently accrues at, including boosts
+    pub fn effective_apy(&self) -> u16 {
+        self.apy.saturating_add(self.boost_bps)
+    }
+
+    // APY rewards accrue at: nominal, or real (nominal minus reported inflation) when real-yield accrual is on
+    pub fn accrual_apy(&self, staking_authority: &StakingAuthority) -> u16 {
+        let apy = if staking_authority.real_yield_accrual {
+            real_apy(self.effective_apy(), staking_authority.inflation_bps).max(0) as u16
+        } else {
+            self.effective_apy()
+        };
+        self.clamp_to_band(apy)
+    }
+
+    // Keep an APY within the position's negotiated band, a zero max_apy leaves it uncapped
+    pub fn clamp_to_band(&self, apy: u16) -> u16 {
+        let apy = apy.max(self.min_apy);
+        if self.max_apy > 0 {
+            apy.min(self.max_apy)
+        } else {
+            apy
+        }
+    }
+
+    // Rewards `amount` would earn between now and unlock at the position's nominal APY, within its band
+    pub fn projected_rewards(&self, amount: u64, current_time: i64, seconds_per_year: i64) -> u64 {
+        let remaining = (self.unlock_time - current_time).max(0) as u128;
+        let apy = self.clamp_to_band(self.effective_apy());
+        (amount as u128 * apy as u128 * remaining / (10000 * seconds_per_year as u128)) as u64
+    }
+
+    // Move the unlock time later and commit the rewards the extra lock time will earn, returning the added liability
+    pub fn extend_unlock(&mut self, new_unlock_time: i64, current_time: i64, seconds_per_year: i64) -> u64 {
+        let liability_before = self.projected_rewards(self.deposit_amount, current_time, seconds_per_year);
+        self.unlock_time = new_unlock_time;
+        let added_liability = self
+            .projected_rewards(self.deposit_amount, current_time, seconds_per_year)
+            .saturating_sub(liability_before);
+        self.reward_liability = self.reward_liability.checked_add(added_liability).unwrap();
+        added_liability
+    }
+
+    // With auto_relock_on_claim, push the unlock forward by the position's original lock term on
+    // every paying claim, returning the added liability. Unbonding positions are already exiting.
+    pub fn relock_on_claim(&mut self, staking_authority: &StakingAuthority, paid: u64, current_time: i64) -> Result<u64> {
+        if !self.auto_relock_on_claim || self.unbonding || paid == 0 {
+            return Ok(0);
+        }
+        let new_unlock_time = self.unlock_time.checked_add(self.lock_duration_seconds).unwrap();
+        staking_authority.check_unlock_horizon(new_unlock_time, current_time)?;
+        Ok(self.extend_unlock(new_unlock_time, current_time, staking_authority.year_seconds()))
+    }
+
+    // Add the projected rewards for newly staked principal to the position's liability
+    pub fn commit_liability(&mut self, amount: u64, current_time: i64, seconds_per_year: i64) -> u64 {
+        let liability = self.projected_rewards(amount, current_time, seconds_per_year);
+        self.reward_liability = self.reward_liability.checked_add(liability).unwrap();
+        liability
+    }
+
+    // Release liability covered by paid rewards, returning the amount released
+    pub fn release_liability(&mut self, paid: u64) -> u64 {
+        let released = paid.min(self.reward_liability);
+        self.reward_liability -= released;
+        released
+    }
+
+    // Seconds elapsed since the last accrual update, derived from slots when a slot estimate is set
+    // so that accrual does not depend on validator-reported timestamps. Accrual stops at the emission cap
+    // or when the tier's emission budget runs out, and excludes time the validator spent jailed and time
+    // the position's tier spent paused.
+    // Nothing accrues while the position is unbonding.
+    pub fn accrual_elapsed(&self, staking_authority: &StakingAuthority, clock: &Clock) -> u128 {
+        if self.unbonding {
+            return 0;
+        }
+        let (mut end_time, mut end_slot) = if staking_authority.emission_cap_reached() {
+            (
+                clock.unix_timestamp.min(staking_authority.cap_reached_at),
+                clock.slot.min(staking_authority.cap_reached_slot),
+            )
+        } else {
+            (clock.unix_timestamp, clock.slot)
+        };
+        let tier = self.tier_index as usize;
+        if staking_authority.tier_budget_exhausted_at[tier] > 0 {
+            end_time = end_time.min(staking_authority.tier_budget_exhausted_at[tier]);
+            end_slot = end_slot.min(staking_authority.tier_budget_exhausted_slot[tier]);
+        }
+        
+        let elapsed = if staking_authority.slots_per_year > 0 {
+            let elapsed_slots = end_slot.saturating_sub(self.accrual_updated_slot) as u128;
+            elapsed_slots * staking_authority.year_seconds() as u128 / staking_authority.slots_per_year as u128
+        } else {
+            (end_time - self.accrual_updated_at).max(0) as u128
+        };
+        let jailed = (staking_authority.jailed_seconds_at(end_time) - self.jailed_seconds_checkpoint).max(0) as u128;
+        let paused = (staking_authority.tier_paused_seconds_at(self.tier_index, end_time)
+            - self.tier_paused_seconds_checkpoint)
+            .max(0) as u128;
+        elapsed.saturating_sub(jailed).saturating_sub(paused)
+    }
+
+    // Principal-seconds accrued over the current reward window up to now
+    pub fn principal_seconds_at(&self, staking_authority: &StakingAuthority, clock: &Clock) -> u128 {
+        let elapsed = self.accrual_elapsed(staking_authority, clock);
+        self.principal_seconds + self.deposit_amount as u128 * elapsed
+    }
+
+    // Fold elapsed time into the accumulator, must run before every principal change
+    pub fn checkpoint_principal(&mut self, staking_authority: &StakingAuthority, clock: &Clock) {
+        self.principal_seconds = self.principal_seconds_at(staking_authority, clock);
+        // An epoch-aligned start still in the future stays in place
+        self.accrual_updated_at = self.accrual_updated_at.max(clock.unix_timestamp);
+        self.accrual_updated_slot = self.accrual_updated_slot.max(clock.slot);
+        self.jailed_seconds_checkpoint = staking_authority.jailed_seconds_at(clock.unix_timestamp);
+        self.tier_paused_seconds_checkpoint = staking_authority.tier_paused_seconds_at(self.tier_index, clock.unix_timestamp);
+    }
+
+    // Rewards for the current window, accrued on the time-weighted average principal
+    pub fn window_rewards(&self, staking_authority: &StakingAuthority, clock: &Clock) -> u64 {
+        let principal_seconds = self.principal_seconds_at(staking_authority, clock);
+        (principal_seconds * self.accrual_apy(staking_authority) as u128
+            / (10000 * staking_authority.year_seconds() as u128)) as u64
+    }
+
+    // Rewards paid and owed so far over principal and time since opening, in basis points a year
+    pub fn realized_apy(&self, staking_authority: &StakingAuthority, clock: &Clock) -> RealizedApyResult {
+        let elapsed_seconds = (clock.unix_timestamp - self.start_time).max(0);
+        if !self.is_active || elapsed_seconds == 0 || self.deposit_amount == 0 {
+            return RealizedApyResult {
+                total_rewards_earned: self.rewards_claimed,
+                elapsed_seconds,
+                realized_apy_bps: 0,
+            };
+        }
+        
+        let total_rewards_earned = self
+            .rewards_claimed
+            .checked_add(self.pending_rewards(staking_authority, clock))
+            .unwrap();
+        let realized_apy_bps = total_rewards_earned as u128 * 10000 * staking_authority.year_seconds() as u128
+            / (self.deposit_amount as u128 * elapsed_seconds as u128);
+        
+        RealizedApyResult {
+            total_rewards_earned,
+            elapsed_seconds,
+            realized_apy_bps: realized_apy_bps.min(u64::MAX as u128) as u64,
+        }
+    }
+
+    // Rewards accrued but not yet paid, plus accrual at the nominal APY from `clock` until unlock
+    pub fn liability(&self, staking_authority: &StakingAuthority, clock: &Clock) -> PositionLiabilityResult {
+        if !self.is_active {
+            return PositionLiabilityResult {
+                accrued_unpaid: 0,
+                future_accrual: 0,
+                total_liability: 0,
+            };
+        }
+        
+        let accrued_unpaid = self.pending_rewards(staking_authority, clock);
+        let future_accrual = self.projected_rewards(
+            self.deposit_amount,
+            clock.unix_timestamp.max(self.accrual_updated_at),
+            staking_authority.year_seconds(),
+        );
+        
+        PositionLiabilityResult {
+            accrued_unpaid,
+            future_accrual,
+            total_liability: accrued_unpaid.checked_add(future_accrual).unwrap(),
+        }
+    }
+
+    // Every input to the reward formula as of `clock`, with the rewards they currently add up to
+    pub fn reward_trace(&self, staking_authority: &StakingAuthority, clock: &Clock) -> RewardTrace {
+        RewardTrace {
+            deposit_amount: self.deposit_amount,
+            apy: self.apy,
+            boost_bps: self.boost_bps,
+            inflation_bps: staking_authority.inflation_bps,
+            real_yield_accrual: staking_authority.real_yield_accrual,
+            min_apy: self.min_apy,
+            max_apy: self.max_apy,
+            accrual_apy: self.accrual_apy(staking_authority),
+            start_time: self.start_time,
+            last_claimed_time: self.last_claimed_time,
+            accrual_updated_at: self.accrual_updated_at,
+            accrual_updated_slot: self.accrual_updated_slot,
+            current_time: clock.unix_timestamp,
+            current_slot: clock.slot,
+            slots_per_year: staking_authority.slots_per_year,
+            seconds_per_year: staking_authority.year_seconds(),
+            accrual_elapsed: self.accrual_elapsed(staking_authority, clock) as u64,
+            principal_seconds: self.principal_seconds,
+            accrued_before_cap: self.accrued_before_cap,
+            accrued_unclaimed: self.accrued_unclaimed,
+            effective_shares: self.effective_shares,
+            reward_debt: self.reward_debt,
+            acc_reward_per_share: staking_authority.reward_index_at(clock.unix_timestamp),
+            rewards_claimed: self.rewards_claimed,
+            cap_reached_at: staking_authority.cap_reached_at,
+            cap_reached_slot: staking_authority.cap_reached_slot,
+            rewards_claimable: staking_authority.rewards_claimable(clock.unix_timestamp),
+            available_rewards: self.pending_rewards(staking_authority, clock),
+        }
+    }
+
+    // All rewards currently owed to the position
+    pub fn pending_rewards(&self, staking_authority: &StakingAuthority, clock: &Clock) -> u64 {
+        self.accrued_before_cap
+            .checked_add(self.accrued_unclaimed)
+            .unwrap()
+            .checked_add(self.window_rewards(staking_authority, clock))
+            .unwrap()
+            .checked_add(self.index_rewards(staking_authority.reward_index_at(clock.unix_timestamp)))
+            .unwrap()
+    }
+
+    // Index emission earned since the position last settled against `acc_reward_per_share`
+    pub fn index_rewards(&self, acc_reward_per_share: u128) -> u64 {
+        let accumulated = self.effective_shares * acc_reward_per_share / REWARD_INDEX_PRECISION;
+        accumulated.saturating_sub(self.reward_debt) as u64
+    }
+
+    // Settle index rewards into accrued_unclaimed and resize the position's effective shares
+    // to its current principal and tier, dropping out of the index once inactive
+    pub fn sync_reward_index(&mut self, staking_authority: &mut StakingAuthority, clock: &Clock) {
+        staking_authority.update_reward_index(clock.unix_timestamp);
+        let acc_reward_per_share = staking_authority.acc_reward_per_share;
+        
+        let rewards = self.index_rewards(acc_reward_per_share);
+        self.accrued_unclaimed = self.accrued_unclaimed.checked_add(rewards).unwrap();
+        self.index_rewards_earned = self.index_rewards_earned.checked_add(rewards).unwrap();
+        self.reward_liability = self.reward_liability.checked_add(rewards).unwrap();
+        
+        let effective_shares = if self.is_active && !self.unbonding {
+            self.deposit_amount as u128 * APY_TIERS[self.tier_index as usize].weight_multiplier as u128 / 10000
+        } else {
+            0
+        };
+        staking_authority.total_effective_shares = staking_authority
+            .total_effective_shares
+            .checked_sub(self.effective_shares)
+            .unwrap()
+            .checked_add(effective_shares)
+            .unwrap();
+        self.effective_shares = effective_shares;
+        self.reward_debt = effective_shares * acc_reward_per_share / REWARD_INDEX_PRECISION;
+        self.refresh_accrual_rate(staking_authority);
+    }
+
+    // Cache the tokens per day the position earns at its current APY, for display only.
+    // Index emission is excluded since it depends on every other position's shares.
+    pub fn refresh_accrual_rate(&mut self, staking_authority: &StakingAuthority) {
+        self.last_accrual_rate = if self.is_active && !self.unbonding {
+            (self.deposit_amount as u128 * self.accrual_apy(staking_authority) as u128 * 86400
+                / (10000 * staking_authority.year_seconds() as u128)) as u64
+        } else {
+            0
+        };
+    }
+
+    // Settle everything accrued up to now and stop accruing until the cooldown ends. Lock time
+    // must already be served, unbonding is the exit.
+    pub fn begin_unbonding(&mut self, staking_authority: &mut StakingAuthority, clock: &Clock) -> Result<()> {
+        if self.unbonding {
+            return err!(StakingError::PositionUnbonding);
+        }
+        let unbonding_seconds = staking_authority.unbonding_seconds;
+        if unbonding_seconds == 0 {
+            return err!(StakingError::UnbondingDisabled);
+        }
+        if clock.unix_timestamp < self.unlock_time {
+            return err!(StakingError::StakingPeriodNotEnded);
+        }
+        
+        self.settle_pending(staking_authority, clock);
+        self.unbonding = true;
+        self.cooldown_end = clock.unix_timestamp + unbonding_seconds;
+        self.sync_reward_index(staking_authority, clock);
+        Ok(())
+    }
+
+    // Settle the accrual window for emit_accrual_checkpoint, at most once per ACCRUAL_CHECKPOINT_INTERVAL,
+    // returning the rewards accrued to now
+    pub fn checkpoint_accrual(&mut self, staking_authority: &StakingAuthority, clock: &Clock) -> Result<u64> {
+        if !self.is_active {
+            return err!(StakingError::InactiveStake);
+        }
+        if clock.unix_timestamp - self.last_checkpoint_at < ACCRUAL_CHECKPOINT_INTERVAL {
+            return err!(StakingError::CheckpointTooSoon);
+        }
+        
+        self.settle_before_cap(staking_authority, clock);
+        self.settle_rewards(staking_authority, clock);
+        self.last_checkpoint_at = clock.unix_timestamp;
+        Ok(self.pending_rewards(staking_authority, clock))
+    }
+
+    // For recover_position: store the bump that derives `address` from the owner's seeds, if there is
+    // one, returning whether it was restored. Positions already at their stored-bump PDA are rejected.
+    pub fn restore_bump(&mut self, address: &Pubkey, staking_authority: &Pubkey, token_mint: &Pubkey) -> Result<bool> {
+        if self.stake_authority != *staking_authority {
+            return err!(StakingError::InvalidStakeAccount);
+        }
+        let derives_address = |bump: u8| {
+            Pubkey::create_program_address(
+                &[b"stake_account", self.owner.as_ref(), token_mint.as_ref(), &[bump]],
+                &crate::ID,
+            )
+            .ok()
+                == Some(*address)
+        };
+        if derives_address(self.bump) {
+            return err!(StakingError::PositionNotRecoverable);
+        }
+        
+        match (0..=u8::MAX).rev().find(|bump| derives_address(*bump)) {
+            Some(bump) => {
+                self.bump = bump;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Increases are at least min_increase_interval apart, counted from opening for the first one
+    pub fn check_increase_interval(&self, staking_authority: &StakingAuthority, current_time: i64) -> Result<()> {
+        if current_time - self.last_increased_at < staking_authority.min_increase_interval {
+            return err!(StakingError::IncreaseTooSoon);
+        }
+        Ok(())
+    }
+
+    // With an unbonding period, principal is only withdrawn from an unbonding position past its cooldown
+    pub fn check_cooldown_ended(&self, staking_authority: &StakingAuthority, current_time: i64) -> Result<()> {
+        if staking_authority.unbonding_seconds > 0 && (!self.unbonding || current_time < self.cooldown_end) {
+            return err!(StakingError::CooldownNotEnded);
+        }
+        Ok(())
+    }
+
+    // The beneficiary may act once the owner has been inactive for longer than the threshold
+    pub fn check_owner_inactive(&self, staking_authority: &StakingAuthority, current_time: i64) -> Result<()> {
+        let inactivity_seconds = staking_authority.beneficiary_inactivity_seconds;
+        if inactivity_seconds == 0 {
+            return err!(StakingError::BeneficiaryClaimsDisabled);
+        }
+        if current_time - self.owner_last_active <= inactivity_seconds {
+            return err!(StakingError::OwnerStillActive);
+        }
+        Ok(())
+    }
+
+    // Settle index, pre-cap and window rewards, returning everything now owed
+    pub fn settle_pending(&mut self, staking_authority: &mut StakingAuthority, clock: &Clock) -> u64 {
+        self.sync_reward_index(staking_authority, clock);
+        self.settle_before_cap(staking_authority, clock);
+        self.settle_rewards(staking_authority, clock);
+        self.pending_rewards(staking_authority, clock)
+    }
+
+    // Move rewards accrued in the current window into accrued_unclaimed
+    pub fn settle_rewards(&mut self, staking_authority: &StakingAuthority, clock: &Clock) {
+        let rewards = self.window_rewards(staking_authority, clock);
+        self.accrued_unclaimed = self.accrued_unclaimed.checked_add(rewards).unwrap();
+        self.fold_window(staking_authority, clock);
+    }
+
+    // Once the emission cap is reached, move the position's pre-cap accrual into accrued_before_cap
+    pub fn settle_before_cap(&mut self, staking_authority: &StakingAuthority, clock: &Clock) {
+        if !staking_authority.emission_cap_reached()
+            || self.accrual_updated_at >= staking_authority.cap_reached_at
+        {
+            return;
+        }
+        
+        let rewards = self.window_rewards(staking_authority, clock);
+        self.accrued_before_cap = self.accrued_before_cap.checked_add(rewards).unwrap();
+        self.fold_window(staking_authority, clock);
+    }
+
+    // Pay settled rewards out of the position and add them back as principal, moving the
+    // liability from the paid rewards to what the new principal earns through unlock
+    pub fn compound_rewards(&mut self, staking_authority: &mut StakingAuthority, amount: u64, clock: &Clock) {
+        self.take_rewards(amount, clock);
+        self.record_claim(amount, true, clock);
+        let released_liability = self.release_liability(amount);
+        self.checkpoint_principal(staking_authority, clock);
+        self.deposit_amount = self.deposit_amount.checked_add(amount).unwrap();
+        let added_liability = self.commit_liability(amount, clock.unix_timestamp, staking_authority.year_seconds());
+        self.sync_reward_index(staking_authority, clock);
+        
+        // Update staking stats
+        staking_authority.total_staked = staking_authority.total_staked.checked_add(amount).unwrap();
+        staking_authority.add_tier_stake(self.tier_index, amount);
+        staking_authority.committed_liability = staking_authority
+            .committed_liability
+            .saturating_sub(released_liability)
+            .checked_add(added_liability)
+            .unwrap();
+        staking_authority.record_emission(amount, clock);
+        staking_authority.record_tier_emission(self.tier_index, amount, clock);
+    }
+
+    // Earliest boundary after `current_time`, ties going to the first listed kind
+    pub fn next_event(&self, staking_authority: &StakingAuthority, current_time: i64) -> (i64, NextEventKind) {
+        if !self.is_active {
+            return (0, NextEventKind::None);
+        }
+
+        let claim_cap_reset = if self.epoch_claim_allowance(staking_authority, current_time) == 0 {
+            staking_authority.next_epoch_start(current_time + 1)
+        } else {
+            0
+        };
+        let cooldown_end = if self.unbonding { self.cooldown_end } else { 0 };
+        let boundaries = [
+            (self.accrual_updated_at, NextEventKind::AccrualStart),
+            (self.start_time + staking_authority.cooling_off_seconds, NextEventKind::CoolingOffEnd),
+            (claim_cap_reset, NextEventKind::ClaimCapReset),
+            (self.unlock_time, NextEventKind::Unlock),
+            (cooldown_end, NextEventKind::CooldownEnd),
+        ];
+        boundaries
+            .iter()
+            .copied()
+            .filter(|(timestamp, _)| *timestamp > current_time)
+            .min_by_key(|(timestamp, _)| *timestamp)
+            .unwrap_or((0, NextEventKind::None))
+    }
+
+    // Split pending rewards for claim_and_compound. The pool's reserve bounds the total, claim_bps of it
+    // is paid out up to the epoch allowance and the rest is restaked, any excess stays owed. A payout
+    // must meet the same minimum as claim_rewards, a pure compound pays nothing out.
+    pub fn claim_split(
+        &self,
+        staking_authority: &StakingAuthority,
+        pending: u64,
+        pool_balance: u64,
+        claim_bps: u16,
+        current_time: i64,
+    ) -> Result<(u64, u64)> {
+        let rewards = staking_authority.max_payout_within_reserve(pool_balance, self.reward_liability, pending);
+        let claimed = (rewards as u128 * claim_bps as u128 / 10000) as u64;
+        let compounded = rewards - claimed;
+        let claimed = claimed.min(self.epoch_claim_allowance(staking_authority, current_time));
+        if claim_bps > 0 {
+            staking_authority.check_min_claim(claimed)?;
+        }
+        Ok((claimed, compounded))
+    }
+
+    // Slash slash_bps of the principal after settling what the unslashed principal earned, along with
+    // settled rewards when slash_includes_rewards is set. Returns the principal and rewards removed.
+    pub fn slash(&mut self, staking_authority: &mut StakingAuthority, slash_bps: u16, clock: &Clock) -> (u64, u64) {
+        self.settle_pending(staking_authority, clock);
+        let slashed = (self.deposit_amount as u128 * slash_bps as u128 / 10000) as u64;
+        
+        // Drop the future rewards the slashed principal would have earned
+        let slashed_liability = self.projected_rewards(slashed, clock.unix_timestamp, staking_authority.year_seconds());
+        let mut released_liability = self.release_liability(slashed_liability);
+        self.deposit_amount -= slashed;
+        
+        // Cut settled rewards pro-rata when configured
+        let rewards_slashed = if staking_authority.slash_includes_rewards {
+            self.slash_settled_rewards(slash_bps)
+        } else {
+            0
+        };
+        released_liability += self.release_liability(rewards_slashed);
+        self.sync_reward_index(staking_authority, clock);
+        
+        // Update staking stats
+        staking_authority.total_staked = staking_authority.total_staked.checked_sub(slashed).unwrap();
+        staking_authority.remove_tier_stake(self.tier_index, slashed);
+        staking_authority.committed_liability = staking_authority.committed_liability.saturating_sub(released_liability);
+        (slashed, rewards_slashed)
+    }
+
+    // Cut settled, unclaimed rewards by slash_bps, returning the amount removed
+    pub fn slash_settled_rewards(&mut self, slash_bps: u16) -> u64 {
+        let slash = |amount: u64| (amount as u128 * slash_bps as u128 / 10000) as u64;
+        let from_before_cap = slash(self.accrued_before_cap);
+        let from_unclaimed = slash(self.accrued_unclaimed);
+        self.accrued_before_cap -= from_before_cap;
+        self.accrued_unclaimed -= from_unclaimed;
+        from_before_cap + from_unclaimed
+    }
+
+    // Append a payout to the claim history, overwriting the oldest once full
+    pub fn record_claim(&mut self, amount: u64, compounded: bool, clock: &Clock) {
+        if amount == 0 {
+            return;
+        }
+        self.claim_history[(self.claim_history_count % CLAIM_HISTORY_LEN as u64) as usize] = ClaimRecord {
+            timestamp: clock.unix_timestamp,
+            amount,
+            compounded,
+        };
+        self.claim_history_count = self.claim_history_count.checked_add(1).unwrap();
+    }
+
+    // Count a claim made before unlock, claims that paid nothing keep the hodler bonus
+    pub fn count_interim_claim(&mut self, paid: u64, clock: &Clock) {
+        if paid > 0 && clock.unix_timestamp < self.unlock_time {
+            self.interim_claims = self.interim_claims.saturating_add(1);
+        }
+    }
+
+    // How much more the position may be paid this epoch under max_claim_per_epoch
+    pub fn epoch_claim_allowance(&self, staking_authority: &StakingAuthority, current_time: i64) -> u64 {
+        let cap = staking_authority.max_claim_per_epoch;
+        if cap == 0 {
+            u64::MAX
+        } else if self.claim_epoch != staking_authority.current_epoch(current_time) {
+            cap
+        } else {
+            cap.saturating_sub(self.claimed_this_epoch)
+        }
+    }
+
+    // Count a payout against the current epoch, starting a fresh count on rollover
+    pub fn record_epoch_claim(&mut self, staking_authority: &StakingAuthority, amount: u64, current_time: i64) {
+        let epoch = staking_authority.current_epoch(current_time);
+        if self.claim_epoch != epoch {
+            self.claim_epoch = epoch;
+            self.claimed_this_epoch = 0;
+        }
+        self.claimed_this_epoch = self.claimed_this_epoch.saturating_add(amount);
+    }
+
+    // Recorded payouts still in the ring buffer, oldest first
+    pub fn claim_records(&self) -> impl Iterator<Item = ClaimRecord> + '_ {
+        let kept = self.claim_history_count.min(CLAIM_HISTORY_LEN as u64);
+        (self.claim_history_count - kept..self.claim_history_count)
+            .map(move |index| self.claim_history[(index % CLAIM_HISTORY_LEN as u64) as usize])
+    }
+
+    // Settle rewards at the current tier, then apply the new tier's APY from now and its minimum
+    // lock, committing the extra liability the longer lock adds
+    pub fn move_to_tier(&mut self, staking_authority: &mut StakingAuthority, new_tier_index: u8, clock: &Clock) {
+        let old_tier_index = self.tier_index;
+        let new_unlock_time = self.unlock_time_for_tier(new_tier_index, clock.unix_timestamp);
+        self.settle_pending(staking_authority, clock);
+        let seconds_per_year = staking_authority.year_seconds();
+        let liability_before = self.projected_rewards(self.deposit_amount, clock.unix_timestamp, seconds_per_year);
+        self.tier_index = new_tier_index;
+        self.tier_paused_seconds_checkpoint = staking_authority.tier_paused_seconds_at(new_tier_index, clock.unix_timestamp);
+        self.apy = staking_authority.tier_apy(new_tier_index, clock.unix_timestamp);
+        self.unlock_time = new_unlock_time;
+        self.owner_last_active = clock.unix_timestamp;
+        self.sync_reward_index(staking_authority, clock);
+        let added_liability = self
+            .projected_rewards(self.deposit_amount, clock.unix_timestamp, seconds_per_year)
+            .saturating_sub(liability_before);
+        self.reward_liability = self.reward_liability.checked_add(added_liability).unwrap();
+        
+        // Update tier and liability stats
+        staking_authority.remove_tier_stake(old_tier_index, self.deposit_amount);
+        staking_authority.add_tier_stake(new_tier_index, self.deposit_amount);
+        staking_authority.committed_liability = staking_authority.committed_liability.checked_add(added_liability).unwrap();
+    }
+
+    // Deduct paid rewards from settled balances, pre-cap rewards first
+    pub fn take_rewards(&mut self, amount: u64, clock: &Clock) {
+        let from_before_cap = amount.min(self.accrued_before_cap);
+        self.accrued_before_cap -= from_before_cap;
+        self.accrued_unclaimed = self.accrued_unclaimed.checked_sub(amount - from_before_cap).unwrap();
+        self.rewards_claimed = self.rewards_claimed.checked_add(amount).unwrap();
+        self.last_claimed_time = clock.unix_timestamp;
+    }
+
+    // Close the current accrual window into the lifetime accumulator
+    fn fold_window(&mut self, staking_authority: &StakingAuthority, clock: &Clock) {
+        let principal_seconds = self.principal_seconds_at(staking_authority, clock);
+        self.lifetime_reward_units = self
+            .lifetime_reward_units
+            .checked_add(principal_seconds * self.accrual_apy(staking_authority) as u128)
+            .unwrap();
+        self.principal_seconds = 0;
+        self.accrual_updated_at = self.accrual_updated_at.max(clock.unix_timestamp);
+        self.accrual_updated_slot = self.accrual_updated_slot.max(clock.slot);
+        self.jailed_seconds_checkpoint = staking_authority.jailed_seconds_at(clock.unix_timestamp);
+        self.tier_paused_seconds_checkpoint = staking_authority.tier_paused_seconds_at(self.tier_index, clock.unix_timestamp);
+    }
+
+    // What was paid beyond the entitlement, the most clawback_rewards may recover
+    pub fn over_distributed(&self, staking_authority: &StakingAuthority, clock: &Clock) -> u64 {
+        self.rewards_claimed.saturating_sub(self.entitled_rewards(staking_authority, clock))
+    }
+
+    // Everything the position has earned: advertised APY accrual, settled index emission and any hodler bonus
+    pub fn entitled_rewards(&self, staking_authority: &StakingAuthority, clock: &Clock) -> u64 {
+        self.advertised_lifetime_rewards(staking_authority, clock)
+            .checked_add(self.index_rewards_earned)
+            .unwrap()
+            .checked_add(self.bonus_rewards_paid)
+            .unwrap()
+    }
+
+    // Lifetime rewards at the advertised APY, without the rounding applied on each claim
+    pub fn advertised_lifetime_rewards(&self, staking_authority: &StakingAuthority, clock: &Clock) -> u64 {
+        let window_units = self.principal_seconds_at(staking_authority, clock) * self.accrual_apy(staking_authority) as u128;
+        let units = self.lifetime_reward_units.checked_add(window_units).unwrap();
+        (units / (10000 * staking_authority.year_seconds() as u128)) as u64
+    }
+
+    // A stake can be cancelled for a full refund up to cooling_off_seconds after it opened
+    pub fn in_cooling_off(&self, staking_authority: &StakingAuthority, current_time: i64) -> bool {
+        current_time - self.start_time <= staking_authority.cooling_off_seconds
+    }
+
+    // Early exit is only for locked positions, and some tiers do not allow leaving early at all.
+    // Past unlock the regular unstake applies.
+    pub fn check_early_exit(&self, current_time: i64) -> Result<()> {
+        if current_time >= self.unlock_time {
+            return err!(StakingError::StakingPeriodEnded);
+        }
+        if APY_TIERS[self.tier_index as usize].no_early_exit {
+            return err!(StakingError::EarlyExitForbidden);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use anchor_spl::token::spl_token::state::AccountState;
+
+    const NOW: i64 = 1_700_006_400;
+    const DAY: i64 = 86400;
+
+    // Accounts as a freshly allocated, zero-filled account decodes
+    fn staking_authority() -> StakingAuthority {
+        StakingAuthority::deserialize(&mut &vec![0u8; StakingAuthority::SIZE][..]).unwrap()
+    }
+
+    fn stake_account() -> StakeAccount {
+        StakeAccount::deserialize(&mut &vec![0u8; StakeAccount::SIZE][..]).unwrap()
+    }
+
+    fn clock_at(unix_timestamp: i64) -> Clock {
+        Clock { unix_timestamp, ..Clock::default() }
+    }
+
+    // A position opened at `start` the way stake does, at the tier's APY
+    fn open_position(amount: u64, lock_period_days: u16, start: i64) -> StakeAccount {
+        let mut position = stake_account();
+        let apy = APY_TIERS[tier_for_lock_period(lock_period_days)].apy_bps;
+        position.open(amount, lock_period_days, apy, SECONDS_PER_YEAR, &clock_at(start));
+        position
+    }
+
+    fn assert_error<T>(result: Result<T>, expected: StakingError) {
+        match result {
+            Ok(_) => panic!("expected {:?}", expected),
+            Err(error) => assert_eq!(error, anchor_lang::error::Error::from(expected)),
+        }
+    }
+
+    fn stake_account_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"stake_account", owner.as_ref(), mint.as_ref()], &crate::ID).0
+    }
+
+    // A token account as the token program stores it
+    fn token_account(mint: Pubkey, owner: Pubkey, delegate: Option<Pubkey>, state: AccountState) -> TokenAccount {
+        let account = anchor_spl::token::spl_token::state::Account {
+            mint,
+            owner,
+            delegate: delegate.map_or(COption::None, COption::Some),
+            state,
+            ..Default::default()
+        };
+        let mut data = vec![0u8; anchor_spl::token::spl_token::state::Account::LEN];
+        Pack::pack(account, &mut data).unwrap();
+        TokenAccount::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    #[test]
+    fn stake_accounts_for_two_mints_do_not_collide() {
+        let owner = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+        assert_ne!(stake_account_address(&owner, &mint_a), stake_account_address(&owner, &mint_b));
+        assert_eq!(stake_account_address(&owner, &mint_a), stake_account_address(&owner, &mint_a));
+    }
+
+    #[test]
+    fn lock_periods_map_to_the_highest_tier_reached() {
+        assert_eq!(tier_for_lock_period(0), 0);
+        assert_eq!(tier_for_lock_period(89), 0);
+        assert_eq!(tier_for_lock_period(90), 1);
+        assert_eq!(tier_for_lock_period(364), 2);
+        assert_eq!(tier_for_lock_period(365), 3);
+        assert_eq!(tier_for_lock_period(u16::MAX), 3);
+    }
+
+    #[test]
+    fn no_early_exit_tier_forbids_leaving_before_unlock() {
+        let position = open_position(1_000, 365, NOW);
+        assert_error(position.check_early_exit(NOW + DAY), StakingError::EarlyExitForbidden);
+        assert_error(position.check_early_exit(position.unlock_time), StakingError::StakingPeriodEnded);
+    }
+
+    #[test]
+    fn other_tiers_allow_early_exit_until_unlock() {
+        let position = open_position(1_000, 180, NOW);
+        assert!(position.check_early_exit(NOW + DAY).is_ok());
+        assert!(position.check_early_exit(position.unlock_time - 1).is_ok());
+        assert_error(position.check_early_exit(position.unlock_time), StakingError::StakingPeriodEnded);
+    }
+
+    #[test]
+    fn rewards_accrue_on_time_weighted_principal() {
+        let staking_authority = staking_authority();
+        let half_year = SECONDS_PER_YEAR / 2;
+        
+        // Principal doubles halfway through a year at 15%
+        let mut position = open_position(1_000_000, 365, NOW);
+        position.checkpoint_principal(&staking_authority, &clock_at(NOW + half_year));
+        position.deposit_amount += 1_000_000;
+        
+        let rewards = position.window_rewards(&staking_authority, &clock_at(NOW + SECONDS_PER_YEAR));
+        assert_eq!(rewards, 75_000 + 150_000);
+    }
+
+    #[test]
+    fn unchanged_principal_earns_the_plain_apy() {
+        let staking_authority = staking_authority();
+        let position = open_position(1_000_000, 365, NOW);
+        assert_eq!(position.window_rewards(&staking_authority, &clock_at(NOW + SECONDS_PER_YEAR)), 150_000);
+    }
+
+    #[test]
+    fn total_staked_delta_applies_both_signs() {
+        assert_eq!(apply_total_staked_delta(1_000, 250), Some(1_250));
+        assert_eq!(apply_total_staked_delta(1_000, -250), Some(750));
+        assert_eq!(apply_total_staked_delta(1_000, -1_000), Some(0));
+        assert_eq!(apply_total_staked_delta(1_000, 0), Some(1_000));
+    }
+
+    #[test]
+    fn total_staked_delta_rejects_underflow_and_overflow() {
+        assert_eq!(apply_total_staked_delta(1_000, -1_001), None);
+        assert_eq!(apply_total_staked_delta(0, i64::MIN), None);
+        assert_eq!(apply_total_staked_delta(u64::MAX, 1), None);
+    }
+
+    #[test]
+    fn slot_based_accrual_starts_at_the_deposit_slot() {
+        let mut staking_authority = staking_authority();
+        staking_authority.slots_per_year = DEFAULT_SLOTS_PER_YEAR;
+        let slots_per_day = DEFAULT_SLOTS_PER_YEAR / 365;
+        
+        let mut position = stake_account();
+        let open_clock = Clock { unix_timestamp: NOW, slot: 1_000, ..Clock::default() };
+        position.open(1_000_000, 365, 1500, SECONDS_PER_YEAR, &open_clock);
+        assert_eq!(position.start_slot, 1_000);
+        
+        // A skewed validator timestamp does not change accrual, the slots elapsed do
+        let clock = Clock { unix_timestamp: NOW + 10 * DAY, slot: 1_000 + slots_per_day, ..Clock::default() };
+        assert_eq!(position.accrual_elapsed(&staking_authority, &clock), DAY as u128);
+    }
+
+    #[test]
+    fn rewards_stay_claimable_through_the_cap_grace_window() {
+        let mut staking_authority = staking_authority();
+        assert!(staking_authority.rewards_claimable(NOW));
+        
+        staking_authority.cap_reached_at = NOW;
+        staking_authority.cap_grace_seconds = 7 * DAY;
+        assert!(staking_authority.rewards_claimable(NOW + 7 * DAY));
+        assert!(!staking_authority.rewards_claimable(NOW + 7 * DAY + 1));
+    }
+
+    #[test]
+    fn accrual_stops_at_the_emission_cap() {
+        let mut staking_authority = staking_authority();
+        let position = open_position(1_000_000, 365, NOW);
+        staking_authority.cap_reached_at = NOW + DAY;
+        assert_eq!(position.accrual_elapsed(&staking_authority, &clock_at(NOW + 30 * DAY)), DAY as u128);
+    }
+
+    // Settle and pay out everything owed once a day for `days` days
+    fn claim_daily(position: &mut StakeAccount, staking_authority: &StakingAuthority, days: i64) {
+        for day in 1..=days {
+            let clock = clock_at(position.start_time + day * DAY);
+            position.settle_rewards(staking_authority, &clock);
+            let paid = position.accrued_unclaimed;
+            position.take_rewards(paid, &clock);
+        }
+    }
+
+    #[test]
+    fn entitlement_tracks_rewards_lost_to_rounding() {
+        let staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 365, NOW);
+        claim_daily(&mut position, &staking_authority, 365);
+        
+        // Each day pays 410 of 410.95 tokens, the top-off owes the difference
+        let clock = clock_at(NOW + 365 * DAY);
+        assert_eq!(position.rewards_claimed, 365 * 410);
+        assert_eq!(position.entitled_rewards(&staking_authority, &clock), 150_000);
+    }
+
+    #[test]
+    fn entitlement_covers_claims_that_always_round_to_zero() {
+        let staking_authority = staking_authority();
+        let mut position = open_position(1_000, 365, NOW);
+        claim_daily(&mut position, &staking_authority, 365);
+        
+        let clock = clock_at(NOW + 365 * DAY);
+        assert_eq!(position.rewards_claimed, 0);
+        assert_eq!(position.entitled_rewards(&staking_authority, &clock), 150);
+    }
+
+    fn reservation(apy: u16, created_at: i64) -> StakeReservation {
+        let mut reservation = StakeReservation::deserialize(&mut &vec![0u8; StakeReservation::SIZE][..]).unwrap();
+        reservation.apy = apy;
+        reservation.created_at = created_at;
+        reservation.valid_until = created_at + 30 * DAY;
+        reservation
+    }
+
+    #[test]
+    fn reserved_apy_holds_after_rates_change() {
+        let mut staking_authority = staking_authority();
+        let reservation = reservation(staking_authority.tier_apy(1, NOW), NOW);
+        
+        staking_authority.scheduled_apy_change = Some((NOW + DAY, 300));
+        assert_eq!(staking_authority.tier_apy(1, NOW + 2 * DAY), 600);
+        assert_eq!(reservation.apy_at(NOW + 2 * DAY), 800);
+    }
+
+    #[test]
+    fn only_payouts_beyond_the_entitlement_are_over_distributed() {
+        let staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 365, NOW);
+        let clock = clock_at(NOW + SECONDS_PER_YEAR);
+        position.settle_rewards(&staking_authority, &clock);
+        
+        position.take_rewards(150_000, &clock);
+        assert_eq!(position.over_distributed(&staking_authority, &clock), 0);
+        
+        // A payout credited twice by mistake
+        position.rewards_claimed += 20_000;
+        assert_eq!(position.over_distributed(&staking_authority, &clock), 20_000);
+    }
+
+    #[test]
+    fn payouts_keep_the_pool_reserve() {
+        let mut staking_authority = staking_authority();
+        staking_authority.committed_liability = 1_000;
+        assert_eq!(staking_authority.max_payout_within_reserve(600, 1_000, 500), 500);
+        
+        // 50% reserve: the pool must keep half of the liability left after the payout
+        staking_authority.reserve_ratio_bps = 5000;
+        assert_eq!(staking_authority.max_payout_within_reserve(1_000, 100, 100), 100);
+        assert_eq!(staking_authority.max_payout_within_reserve(600, 1_000, 500), 200);
+        assert_eq!(staking_authority.max_payout_within_reserve(600, 0, 500), 100);
+    }
+
+    #[test]
+    fn pool_below_the_reserve_pays_nothing() {
+        let mut staking_authority = staking_authority();
+        staking_authority.committed_liability = 1_000;
+        staking_authority.reserve_ratio_bps = 5000;
+        assert_eq!(staking_authority.max_payout_within_reserve(400, 0, 100), 0);
+        assert!(staking_authority.is_undercollateralized(400));
+        assert!(!staking_authority.is_undercollateralized(500));
+    }
+
+    #[test]
+    fn lp_position_records_parse_owner_and_open_time() {
+        let owner = Pubkey::new_unique();
+        let mut data = vec![0u8; LpPositionRecord::LEN];
+        data[8..40].copy_from_slice(owner.as_ref());
+        data[40..48].copy_from_slice(&NOW.to_le_bytes());
+        
+        let record = LpPositionRecord::parse(&data).unwrap();
+        assert_eq!(record.owner, owner);
+        assert_eq!(record.opened_at, NOW);
+        assert_error(LpPositionRecord::parse(&data[..LpPositionRecord::LEN - 1]), StakingError::InvalidLpPosition);
+    }
+
+    #[test]
+    fn lp_boost_raises_the_accrual_apy() {
+        let staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 365, NOW);
+        position.boost_bps = 500;
+        assert_eq!(position.accrual_apy(&staking_authority), 2000);
+        assert_eq!(position.window_rewards(&staking_authority, &clock_at(NOW + SECONDS_PER_YEAR)), 200_000);
+    }
+
+    #[test]
+    fn merkle_proofs_reach_the_root_from_every_leaf() {
+        let leaves: Vec<[u8; 32]> = (0..4u64)
+            .map(|index| {
+                let position = open_position(1_000 * (index + 1), 90, NOW);
+                position_leaf(&Pubkey::new_unique(), &Pubkey::new_unique(), position.deposit_amount, position.unlock_time)
+            })
+            .collect();
+        let left = merkle_node(&leaves[0], &leaves[1]);
+        let right = merkle_node(&leaves[2], &leaves[3]);
+        let root = merkle_node(&left, &right);
+        
+        assert_eq!(merkle_root_from_proof(leaves[0], &[leaves[1], right]), root);
+        assert_eq!(merkle_root_from_proof(leaves[1], &[leaves[0], right]), root);
+        assert_eq!(merkle_root_from_proof(leaves[2], &[leaves[3], left]), root);
+        assert_eq!(merkle_root_from_proof(leaves[3], &[leaves[2], left]), root);
+        assert_ne!(merkle_root_from_proof(leaves[0], &[leaves[2], right]), root);
+    }
+
+    #[test]
+    fn internal_nodes_do_not_verify_as_positions() {
+        let mut staking_authority = staking_authority();
+        let (key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let leaves = [
+            position_leaf(&key, &owner, 1_000, NOW),
+            position_leaf(&Pubkey::new_unique(), &Pubkey::new_unique(), 2_000, NOW),
+        ];
+        let right = merkle_node(&[7; 32], &[9; 32]);
+        staking_authority.positions_root = merkle_node(&merkle_node(&leaves[0], &leaves[1]), &right);
+        assert!(staking_authority.includes_position(&key, &owner, 1_000, NOW, &[leaves[1], right]));
+        
+        // The left node's children passed off as a position with the proof shortened to its sibling
+        let (lower, higher) = if leaves[0] <= leaves[1] { (leaves[0], leaves[1]) } else { (leaves[1], leaves[0]) };
+        assert!(!staking_authority.includes_position(
+            &Pubkey::new_from_array(lower),
+            &Pubkey::new_from_array(higher),
+            0,
+            0,
+            &[right],
+        ));
+        assert_ne!(merkle_node(&leaves[0], &leaves[1]), keccak::hashv(&[&lower, &higher]).to_bytes());
+    }
+
+    #[test]
+    fn position_leaf_commits_to_principal_and_unlock() {
+        let (key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let leaf = position_leaf(&key, &owner, 1_000, NOW);
+        assert_ne!(position_leaf(&key, &owner, 1_001, NOW), leaf);
+        assert_ne!(position_leaf(&key, &owner, 1_000, NOW + 1), leaf);
+        assert_ne!(position_leaf(&key, &Pubkey::new_unique(), 1_000, NOW), leaf);
+        assert_eq!(position_leaf(&key, &owner, 1_000, NOW), leaf);
+    }
+
+    #[test]
+    fn real_yield_accrues_at_nominal_minus_inflation() {
+        let mut staking_authority = staking_authority();
+        let position = open_position(1_000_000, 365, NOW);
+        staking_authority.inflation_bps = 300;
+        assert_eq!(position.accrual_apy(&staking_authority), 1500);
+        
+        staking_authority.real_yield_accrual = true;
+        assert_eq!(position.accrual_apy(&staking_authority), 1200);
+        staking_authority.inflation_bps = -200;
+        assert_eq!(position.accrual_apy(&staking_authority), 1700);
+        
+        // Inflation above the nominal rate stops accrual rather than going negative
+        staking_authority.inflation_bps = 2000;
+        assert_eq!(real_apy(1500, 2000), -500);
+        assert_eq!(position.accrual_apy(&staking_authority), 0);
+    }
+
+    #[test]
+    fn stakes_can_be_cancelled_only_within_the_cooling_off_window() {
+        let mut staking_authority = staking_authority();
+        staking_authority.cooling_off_seconds = DEFAULT_COOLING_OFF_SECONDS;
+        let position = open_position(1_000, 90, NOW);
+        
+        assert!(position.in_cooling_off(&staking_authority, NOW));
+        assert!(position.in_cooling_off(&staking_authority, NOW + DEFAULT_COOLING_OFF_SECONDS));
+        assert!(!position.in_cooling_off(&staking_authority, NOW + DEFAULT_COOLING_OFF_SECONDS + 1));
+        
+        staking_authority.cooling_off_seconds = 0;
+        assert!(!position.in_cooling_off(&staking_authority, NOW + 1));
+    }
+
+    #[test]
+    fn index_emission_is_shared_by_tier_weight_and_committed() {
+        let mut staking_authority = staking_authority();
+        staking_authority.reward_rate_per_second = 300;
+        staking_authority.index_updated_at = NOW;
+        
+        // Same principal, 1x and 2x tier weights
+        let mut short_position = open_position(1_000, 30, NOW);
+        let mut long_position = open_position(1_000, 365, NOW);
+        short_position.sync_reward_index(&mut staking_authority, &clock_at(NOW));
+        long_position.sync_reward_index(&mut staking_authority, &clock_at(NOW));
+        assert_eq!(staking_authority.total_effective_shares, 3_000);
+        
+        let short_liability = short_position.reward_liability;
+        let long_liability = long_position.reward_liability;
+        let clock = clock_at(NOW + 100);
+        short_position.sync_reward_index(&mut staking_authority, &clock);
+        long_position.sync_reward_index(&mut staking_authority, &clock);
+        
+        assert_eq!(short_position.index_rewards_earned, 10_000);
+        assert_eq!(long_position.index_rewards_earned, 20_000);
+        assert_eq!(staking_authority.committed_liability, 30_000);
+        assert_eq!(short_position.reward_liability, short_liability + 10_000);
+        assert_eq!(long_position.reward_liability, long_liability + 20_000);
+    }
+
+    #[test]
+    fn index_emits_nothing_without_a_rate() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000, 30, NOW);
+        position.sync_reward_index(&mut staking_authority, &clock_at(NOW));
+        position.sync_reward_index(&mut staking_authority, &clock_at(NOW + DAY));
+        assert_eq!(position.index_rewards_earned, 0);
+        assert_eq!(staking_authority.committed_liability, 0);
+    }
+
+    #[test]
+    fn original_authorities_decode_and_upgrade_with_initialize_defaults() {
+        let mut original = staking_authority();
+        original.authority = Pubkey::new_unique();
+        original.token_mint = Pubkey::new_unique();
+        original.total_staked = 5_000;
+        original.staker_count = 3;
+        
+        // The original layout ends after bumps, the grown account is zero-filled past it
+        let original_len = 32 * 4 + 8 + 8 + 1;
+        let mut data = original.try_to_vec().unwrap();
+        data[original_len..].fill(0);
+        let mut migrated = StakingAuthority::deserialize(&mut &data[..]).unwrap();
+        assert_eq!(migrated.version, 0);
+        assert_eq!(migrated.authority, original.authority);
+        assert_eq!(migrated.total_staked, 5_000);
+        
+        migrated.upgrade_layout();
+        assert_eq!(migrated.version, AUTHORITY_VERSION);
+        assert_eq!(migrated.early_exit_penalty_bps, DEFAULT_EARLY_EXIT_PENALTY_BPS);
+        assert_eq!(migrated.slots_per_year, DEFAULT_SLOTS_PER_YEAR);
+        assert_eq!(migrated.cooling_off_seconds, DEFAULT_COOLING_OFF_SECONDS);
+        assert_eq!(migrated.staker_count, 3);
+    }
+
+    #[test]
+    fn versioned_authorities_keep_their_settings_on_upgrade() {
+        let mut staking_authority = staking_authority();
+        staking_authority.version = 5;
+        staking_authority.early_exit_penalty_bps = 0;
+        staking_authority.slots_per_year = 0;
+        staking_authority.upgrade_layout();
+        assert_eq!(staking_authority.version, AUTHORITY_VERSION);
+        assert_eq!(staking_authority.early_exit_penalty_bps, 0);
+        assert_eq!(staking_authority.slots_per_year, 0);
+    }
+
+    fn tier_apys_at(staking_authority: &StakingAuthority, current_time: i64) -> [u16; TIER_COUNT] {
+        core::array::from_fn(|tier_index| staking_authority.tier_apy(tier_index as u8, current_time))
+    }
+
+    #[test]
+    fn lock_for_target_picks_the_shortest_period_across_tiers() {
+        let tier_apys = tier_apys_at(&staking_authority(), NOW);
+        assert_eq!(tier_apys, [500, 800, 1200, 1500]);
+        
+        // 1% of principal takes 73 days at 5%, 2% no longer fits in the default tier and needs 92 at 8%
+        assert_eq!(lock_for_target(1_000_000, 1, tier_apys, SECONDS_PER_YEAR), Some(1));
+        assert_eq!(lock_for_target(1_000_000, 10_000, tier_apys, SECONDS_PER_YEAR), Some(73));
+        assert_eq!(lock_for_target(1_000_000, 20_000, tier_apys, SECONDS_PER_YEAR), Some(92));
+        assert_eq!(lock_for_target(1, u64::MAX, tier_apys, SECONDS_PER_YEAR), None);
+    }
+
+    #[test]
+    fn lock_for_target_uses_scheduled_rates() {
+        let mut staking_authority = staking_authority();
+        staking_authority.scheduled_apy_change = Some((NOW + DAY, 1000));
+        assert_eq!(lock_for_target(1_000_000, 20_000, tier_apys_at(&staking_authority, NOW), SECONDS_PER_YEAR), Some(92));
+        assert_eq!(lock_for_target(1_000_000, 20_000, tier_apys_at(&staking_authority, NOW + DAY), SECONDS_PER_YEAR), Some(73));
+    }
+
+    #[test]
+    fn compounded_rewards_earn_as_principal() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 365, NOW);
+        staking_authority.total_staked = 1_000_000;
+        staking_authority.committed_liability = position.reward_liability;
+        
+        let clock = clock_at(NOW + SECONDS_PER_YEAR / 2);
+        let pending = position.settle_pending(&mut staking_authority, &clock);
+        assert_eq!(pending, 75_000);
+        position.compound_rewards(&mut staking_authority, pending, &clock);
+        
+        assert_eq!(position.deposit_amount, 1_075_000);
+        assert_eq!(staking_authority.total_staked, 1_075_000);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), 0);
+        assert!(position.claim_records().last().unwrap().compounded);
+        
+        // Liability now covers the rest of the year on the compounded principal
+        assert_eq!(position.reward_liability, 80_625);
+        assert_eq!(staking_authority.committed_liability, 80_625);
+        assert_eq!(position.window_rewards(&staking_authority, &clock_at(NOW + SECONDS_PER_YEAR)), 80_625);
+    }
+
+    #[test]
+    fn dust_claims_wait_for_the_minimum() {
+        let mut staking_authority = staking_authority();
+        assert!(staking_authority.check_min_claim(1).is_ok());
+        
+        staking_authority.min_claimable_rewards = 100;
+        assert_error(staking_authority.check_min_claim(99), StakingError::BelowMinimumClaim);
+        assert!(staking_authority.check_min_claim(100).is_ok());
+    }
+
+    #[test]
+    fn original_positions_migrate_with_their_unclaimed_window() {
+        let mut staking_authority = staking_authority();
+        let quarter_year = SECONDS_PER_YEAR / 4;
+        
+        // An original position claimed halfway through its one-year lock
+        let mut original = stake_account();
+        original.owner = Pubkey::new_unique();
+        original.deposit_amount = 1_000_000;
+        original.start_time = NOW;
+        original.unlock_time = NOW + 365 * DAY;
+        original.apy = 1500;
+        original.rewards_claimed = 75_000;
+        original.last_claimed_time = NOW + 2 * quarter_year;
+        original.is_active = true;
+        
+        // The original layout ends after bump, the grown account is zero-filled past it
+        let original_len = 32 * 3 + 8 + 8 + 8 + 2 + 8 + 8 + 1 + 1;
+        let mut data = original.try_to_vec().unwrap();
+        data[original_len..].fill(0);
+        let mut position = StakeAccount::deserialize(&mut &data[..]).unwrap();
+        assert_eq!(position.formula_version, 0);
+        
+        let clock = clock_at(NOW + 3 * quarter_year);
+        position.migrate_from_original(&mut staking_authority, &clock);
+        assert_eq!(position.tier_index, 3);
+        assert_eq!(position.lock_duration_seconds, 365 * DAY);
+        
+        // Owed: the original formula over the time since the last claim
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), 37_500);
+        assert_eq!(position.entitled_rewards(&staking_authority, &clock), 112_500);
+        assert_eq!(staking_authority.committed_liability, 37_500);
+        assert_eq!(staking_authority.tier_staked[3], 1_000_000);
+        assert_eq!(staking_authority.total_effective_shares, 2_000_000);
+    }
+
+    #[test]
+    fn closed_original_positions_migrate_without_liability() {
+        let mut staking_authority = staking_authority();
+        let mut position = stake_account();
+        position.deposit_amount = 1_000_000;
+        position.start_time = NOW;
+        position.unlock_time = NOW + 90 * DAY;
+        position.rewards_claimed = 10_000;
+        
+        position.migrate_from_original(&mut staking_authority, &clock_at(NOW + 100 * DAY));
+        assert_eq!(position.tier_index, 1);
+        assert_eq!(position.lifetime_reward_units, 10_000 * 10000 * SECONDS_PER_YEAR as u128);
+        assert_eq!(staking_authority.committed_liability, 0);
+        assert_eq!(staking_authority.tier_staked[1], 0);
+    }
+
+    #[test]
+    fn accrual_rate_cache_follows_the_position() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 365, NOW);
+        position.sync_reward_index(&mut staking_authority, &clock_at(NOW));
+        assert_eq!(position.last_accrual_rate, 410);
+        
+        position.deposit_amount = 2_000_000;
+        position.refresh_accrual_rate(&staking_authority);
+        assert_eq!(position.last_accrual_rate, 821);
+        
+        position.unbonding = true;
+        position.refresh_accrual_rate(&staking_authority);
+        assert_eq!(position.last_accrual_rate, 0);
+    }
+
+    #[test]
+    fn tier_vaults_are_isolated_from_other_tiers() {
+        let mut staking_authority = staking_authority();
+        let shared_vault = Pubkey::new_unique();
+        let tier_3_vault = Pubkey::new_unique();
+        staking_authority.tier_vaults[3] = tier_3_vault;
+        assert_eq!(staking_authority.tier_of_vault(&tier_3_vault), Some(3));
+        assert_eq!(staking_authority.tier_of_vault(&shared_vault), None);
+        assert_eq!(staking_authority.tier_of_vault(&Pubkey::default()), None);
+        
+        // A tier with its own vault only uses it, the others can't reach it
+        assert!(staking_authority.check_tier_vault(3, &tier_3_vault).is_ok());
+        assert_error(staking_authority.check_tier_vault(3, &shared_vault), StakingError::InvalidTierVault);
+        assert!(staking_authority.check_tier_vault(0, &shared_vault).is_ok());
+        assert_error(staking_authority.check_tier_vault(0, &tier_3_vault), StakingError::InvalidTierVault);
+        
+        // Principal debt never moves through a tier vault
+        assert!(staking_authority.check_shared_vault(&shared_vault).is_ok());
+        assert_error(staking_authority.check_shared_vault(&tier_3_vault), StakingError::InvalidTierVault);
+    }
+
+    #[test]
+    fn epoch_boundaries_round_up_to_the_next_epoch() {
+        let mut staking_authority = staking_authority();
+        assert_eq!(staking_authority.next_epoch_start(NOW), NOW);
+        assert_eq!(staking_authority.next_epoch_start(NOW + 1), NOW + DAY);
+        assert_eq!(staking_authority.current_epoch(NOW + DAY - 1), staking_authority.current_epoch(NOW));
+        
+        staking_authority.epoch_seconds = 3600;
+        assert_eq!(staking_authority.next_epoch_start(NOW + 1), NOW + 3600);
+    }
+
+    #[test]
+    fn aligned_accrual_starts_at_the_next_epoch() {
+        let mut staking_authority = staking_authority();
+        staking_authority.align_accrual_to_epoch = true;
+        let opened_at = NOW + 3600;
+        let mut position = open_position(1_000_000, 365, opened_at);
+        position.align_accrual_start(&staking_authority, &clock_at(opened_at));
+        assert_eq!(position.accrual_updated_at, NOW + DAY);
+        
+        assert_eq!(position.window_rewards(&staking_authority, &clock_at(NOW + DAY)), 0);
+        assert_eq!(position.accrual_elapsed(&staking_authority, &clock_at(NOW + 2 * DAY)), DAY as u128);
+    }
+
+    #[test]
+    fn backstop_pays_only_up_to_its_cap() {
+        let mut staking_authority = staking_authority();
+        assert_eq!(staking_authority.backstop_remaining(), 0);
+        
+        staking_authority.backstop_cap = 10_000;
+        staking_authority.backstop_used = 7_500;
+        assert_eq!(staking_authority.backstop_remaining(), 2_500);
+        staking_authority.backstop_used = 12_000;
+        assert_eq!(staking_authority.backstop_remaining(), 0);
+    }
+
+    #[test]
+    fn year_basis_scales_accrual() {
+        let mut staking_authority = staking_authority();
+        let position = open_position(1_000_000, 365, NOW);
+        let clock = clock_at(NOW + 360 * DAY);
+        assert_eq!(staking_authority.year_seconds(), SECONDS_PER_YEAR);
+        assert_eq!(position.window_rewards(&staking_authority, &clock), 147_945);
+        
+        // A full year's rate over 360 days on the 360-day basis, slightly less on 365.25 days
+        staking_authority.seconds_per_year = YEAR_BASIS_360_SECONDS;
+        assert_eq!(position.window_rewards(&staking_authority, &clock), 150_000);
+        staking_authority.seconds_per_year = YEAR_BASIS_365_25_SECONDS;
+        assert_eq!(position.window_rewards(&staking_authority, &clock), 147_843);
+    }
+
+    #[test]
+    fn lottery_tiebreaks_are_reproducible_and_reshuffle_each_epoch() {
+        let staking_authority_key = Pubkey::new_unique();
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let seed = lottery_epoch_seed(&staking_authority_key, 7);
+        assert_eq!(seed, lottery_epoch_seed(&staking_authority_key, 7));
+        assert_ne!(seed, lottery_epoch_seed(&staking_authority_key, 8));
+        assert_ne!(seed, lottery_epoch_seed(&Pubkey::new_unique(), 7));
+        
+        assert_eq!(lottery_tiebreak(&first, &seed), lottery_tiebreak(&first, &seed));
+        assert_ne!(lottery_tiebreak(&first, &seed), lottery_tiebreak(&second, &seed));
+        assert_ne!(
+            lottery_tiebreak(&first, &seed),
+            lottery_tiebreak(&first, &lottery_epoch_seed(&staking_authority_key, 8)),
+        );
+    }
+
+    #[test]
+    fn auction_bonus_decays_linearly_to_the_reserved_apy() {
+        let mut reservation = reservation(800, NOW);
+        reservation.auction_bonus_bps = 400;
+        reservation.auction_duration_seconds = 4 * DAY;
+        
+        assert_eq!(reservation.apy_at(NOW - DAY), 1200);
+        assert_eq!(reservation.apy_at(NOW), 1200);
+        assert_eq!(reservation.apy_at(NOW + DAY), 1100);
+        assert_eq!(reservation.apy_at(NOW + 2 * DAY), 1000);
+        assert_eq!(reservation.apy_at(NOW + 4 * DAY), 800);
+        assert_eq!(reservation.apy_at(NOW + 10 * DAY), 800);
+        
+        // Without an auction the bonus is ignored
+        reservation.auction_duration_seconds = 0;
+        assert_eq!(reservation.apy_at(NOW), 800);
+    }
+
+    #[test]
+    fn unlock_horizon_caps_how_far_a_lock_may_reach() {
+        let mut staking_authority = staking_authority();
+        assert!(staking_authority.check_unlock_horizon(NOW + 10_000 * DAY, NOW).is_ok());
+        
+        staking_authority.max_unlock_horizon_seconds = 400 * DAY;
+        assert!(staking_authority.check_unlock_horizon(NOW + 400 * DAY, NOW).is_ok());
+        assert_error(
+            staking_authority.check_unlock_horizon(NOW + 400 * DAY + 1, NOW),
+            StakingError::LockHorizonExceeded,
+        );
+    }
+
+    #[test]
+    fn accrual_apy_stays_within_the_negotiated_band() {
+        let mut position = open_position(1_000_000, 90, NOW);
+        let staking_authority = staking_authority();
+        assert_eq!(position.clamp_to_band(5_000), 5_000);
+        
+        position.min_apy = 1000;
+        position.max_apy = 1200;
+        assert_eq!(position.clamp_to_band(500), 1000);
+        assert_eq!(position.clamp_to_band(1100), 1100);
+        assert_eq!(position.clamp_to_band(5_000), 1200);
+        
+        assert_eq!(position.accrual_apy(&staking_authority), 1000);
+        position.boost_bps = 600;
+        assert_eq!(position.accrual_apy(&staking_authority), 1200);
+    }
+
+    #[test]
+    fn early_exit_penalty_is_charged_on_principal_rounding_down() {
+        assert_eq!(early_exit_penalty(1_000_000, DEFAULT_EARLY_EXIT_PENALTY_BPS), 100_000);
+        assert_eq!(early_exit_penalty(999, DEFAULT_EARLY_EXIT_PENALTY_BPS), 99);
+        assert_eq!(early_exit_penalty(1_000_000, 0), 0);
+        assert_eq!(early_exit_penalty(u64::MAX, 10000), u64::MAX);
+    }
+
+    #[test]
+    fn jailed_time_is_excluded_from_accrual() {
+        let mut staking_authority = staking_authority();
+        let position = open_position(1_000_000, 90, NOW);
+        
+        staking_authority.start_jailed_interval(NOW + 10 * DAY);
+        // Jailing again while jailed keeps the original start
+        staking_authority.start_jailed_interval(NOW + 12 * DAY);
+        assert_eq!(staking_authority.jailed_seconds_at(NOW + 14 * DAY), 4 * DAY);
+        staking_authority.end_jailed_interval(NOW + 15 * DAY);
+        assert_eq!(staking_authority.jailed_seconds_at(NOW + 20 * DAY), 5 * DAY);
+        assert_eq!(position.accrual_elapsed(&staking_authority, &clock_at(NOW + 20 * DAY)), 15 * DAY as u128);
+        
+        // An open interval counts up to the time accrual is read
+        staking_authority.start_jailed_interval(NOW + 18 * DAY);
+        assert_eq!(position.accrual_elapsed(&staking_authority, &clock_at(NOW + 20 * DAY)), 13 * DAY as u128);
+    }
+
+    #[test]
+    fn claim_history_keeps_the_latest_payouts_oldest_first() {
+        let mut position = open_position(1_000_000, 90, NOW);
+        for claim in 1..=20u64 {
+            position.record_claim(claim, claim % 2 == 0, &clock_at(NOW + claim as i64 * DAY));
+            // Claims that paid nothing are not recorded
+            position.record_claim(0, false, &clock_at(NOW + claim as i64 * DAY));
+        }
+        
+        assert_eq!(position.claim_history_count, 20);
+        let records: Vec<ClaimRecord> = position.claim_records().collect();
+        assert_eq!(records.len(), CLAIM_HISTORY_LEN);
+        for (record, claim) in records.iter().zip(5..=20u64) {
+            assert_eq!(record.amount, claim);
+            assert_eq!(record.timestamp, NOW + claim as i64 * DAY);
+            assert_eq!(record.compounded, claim % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn only_paid_claims_before_unlock_forfeit_the_hodler_bonus() {
+        let mut position = open_position(1_000_000, 90, NOW);
+        position.count_interim_claim(0, &clock_at(NOW + DAY));
+        assert_eq!(position.interim_claims, 0);
+        position.count_interim_claim(500, &clock_at(NOW + 90 * DAY));
+        assert_eq!(position.interim_claims, 0);
+        position.count_interim_claim(500, &clock_at(NOW + DAY));
+        assert_eq!(position.interim_claims, 1);
+    }
+
+    #[test]
+    fn paid_hodler_bonus_is_not_over_distributed() {
+        let staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 365, NOW);
+        let clock = clock_at(NOW + SECONDS_PER_YEAR);
+        position.settle_rewards(&staking_authority, &clock);
+        
+        position.take_rewards(150_000, &clock);
+        position.rewards_claimed += 15_000;
+        position.bonus_rewards_paid = 15_000;
+        assert_eq!(position.over_distributed(&staking_authority, &clock), 0);
+    }
+
+    #[test]
+    fn claim_delegate_may_claim_but_not_unstake() {
+        let (owner, delegate, mint) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut position = open_position(1_000_000, 90, NOW);
+        position.owner = owner;
+        assert!(position.is_claim_authority(&owner));
+        assert!(!position.is_claim_authority(&delegate));
+        
+        position.claim_delegate = Some(delegate);
+        assert!(position.is_claim_authority(&owner));
+        assert!(position.is_claim_authority(&delegate));
+        assert!(!position.is_claim_authority(&Pubkey::new_unique()));
+        
+        // Unstake derives the position from its signer, so a delegate never reaches the owner's position
+        assert_ne!(stake_account_address(&delegate, &mint), stake_account_address(&owner, &mint));
+        
+        // Reopening the position drops the delegate
+        position.open(1_000_000, 90, 800, SECONDS_PER_YEAR, &clock_at(NOW + 100 * DAY));
+        assert!(!position.is_claim_authority(&delegate));
+    }
+
+    #[test]
+    fn tier_change_preview_matches_the_moved_position() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        staking_authority.add_tier_stake(1, 1_000_000);
+        let clock = clock_at(NOW + 30 * DAY);
+        
+        position.check_tier_change(2).unwrap();
+        let settled_rewards = position.pending_rewards(&staking_authority, &clock);
+        let new_apy = staking_authority.tier_apy(2, clock.unix_timestamp);
+        let new_unlock_time = position.unlock_time_for_tier(2, clock.unix_timestamp);
+        assert_eq!(settled_rewards, 6_575);
+        assert_eq!(new_unlock_time, NOW + 210 * DAY);
+        
+        position.move_to_tier(&mut staking_authority, 2, &clock);
+        assert_eq!(position.tier_index, 2);
+        assert_eq!(position.apy, new_apy);
+        assert_eq!(position.unlock_time, new_unlock_time);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), settled_rewards);
+        assert_eq!(staking_authority.tier_staked[1], 0);
+        assert_eq!(staking_authority.tier_staked[2], 1_000_000);
+        // 180 days at 12% replaces the 60 days left at 8%
+        assert_eq!(staking_authority.committed_liability, 59_178 - 13_150);
+        
+        assert_error(position.check_tier_change(1), StakingError::InvalidTierChange);
+    }
+
+    #[test]
+    fn epoch_claim_cap_resets_when_the_epoch_rolls_over() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        assert_eq!(position.epoch_claim_allowance(&staking_authority, NOW), u64::MAX);
+        
+        staking_authority.epoch_seconds = DAY;
+        staking_authority.max_claim_per_epoch = 1_000;
+        assert_eq!(position.epoch_claim_allowance(&staking_authority, NOW), 1_000);
+        position.record_epoch_claim(&staking_authority, 600, NOW);
+        position.record_epoch_claim(&staking_authority, 300, NOW + DAY - 1);
+        assert_eq!(position.epoch_claim_allowance(&staking_authority, NOW + DAY - 1), 100);
+        
+        // The next epoch starts a fresh count
+        assert_eq!(position.epoch_claim_allowance(&staking_authority, NOW + DAY), 1_000);
+        position.record_epoch_claim(&staking_authority, 250, NOW + DAY);
+        assert_eq!(position.claimed_this_epoch, 250);
+        assert_eq!(position.epoch_claim_allowance(&staking_authority, NOW + DAY), 750);
+    }
+
+    #[test]
+    fn collateralization_is_pool_balance_over_committed_liability() {
+        let mut staking_authority = staking_authority();
+        assert_eq!(staking_authority.collateralization_bps(0), u64::MAX);
+        
+        staking_authority.committed_liability = 200_000;
+        assert_eq!(staking_authority.collateralization_bps(300_000), 15_000);
+        assert_eq!(staking_authority.collateralization_bps(200_000), 10_000);
+        assert_eq!(staking_authority.collateralization_bps(99_999), 4_999);
+        assert_eq!(staking_authority.collateralization_bps(0), 0);
+    }
+
+    #[test]
+    fn pausing_a_tier_only_stops_its_own_accrual() {
+        let mut staking_authority = staking_authority();
+        let paused = open_position(1_000_000, 90, NOW);
+        let running = open_position(1_000_000, 180, NOW);
+        
+        staking_authority.pause_tier_accrual(1, NOW + 10 * DAY);
+        staking_authority.resume_tier_accrual(1, NOW + 14 * DAY);
+        assert_eq!(staking_authority.tier_paused_seconds_at(1, NOW + 20 * DAY), 4 * DAY);
+        assert_eq!(staking_authority.tier_paused_seconds_at(2, NOW + 20 * DAY), 0);
+        
+        let clock = clock_at(NOW + 20 * DAY);
+        assert_eq!(paused.accrual_elapsed(&staking_authority, &clock), 16 * DAY as u128);
+        assert_eq!(running.accrual_elapsed(&staking_authority, &clock), 20 * DAY as u128);
+    }
+
+    #[test]
+    fn scheduled_apy_applies_to_positions_opened_from_its_date() {
+        let mut staking_authority = staking_authority();
+        assert_eq!(staking_authority.base_apy_at(NOW), APY_TIERS[0].apy_bps);
+        staking_authority.base_apy_bps = 600;
+        staking_authority.scheduled_apy_change = Some((NOW + DAY, 300));
+        assert_eq!(staking_authority.base_apy_at(NOW + DAY - 1), 600);
+        assert_eq!(staking_authority.base_apy_at(NOW + DAY), 300);
+        assert_eq!(tier_apys_at(&staking_authority, NOW), [600, 900, 1300, 1600]);
+        assert_eq!(tier_apys_at(&staking_authority, NOW + DAY), [300, 600, 1000, 1300]);
+        
+        // A position keeps the APY it was opened at across the switch
+        let mut position = stake_account();
+        position.open(1_000_000, 90, staking_authority.tier_apy(1, NOW), SECONDS_PER_YEAR, &clock_at(NOW));
+        assert_eq!(position.accrual_apy(&staking_authority), 900);
+    }
+
+    #[test]
+    fn lock_for_target_skips_tiers_without_yield() {
+        assert_eq!(lock_for_target(1_000_000, 10_000, [0, 800, 1200, 1500], SECONDS_PER_YEAR), Some(90));
+        assert_eq!(lock_for_target(1_000_000, 10_000, [0; TIER_COUNT], SECONDS_PER_YEAR), None);
+    }
+
+    #[test]
+    fn relock_on_claim_extends_the_unlock_by_the_lock_period() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        assert_eq!(position.relock_on_claim(&staking_authority, 100, NOW + 10 * DAY).unwrap(), 0);
+        assert_eq!(position.unlock_time, NOW + 90 * DAY);
+        
+        position.auto_relock_on_claim = true;
+        // 170 days at 8% from the claim replaces the 80 days left
+        assert_eq!(position.relock_on_claim(&staking_authority, 100, NOW + 10 * DAY).unwrap(), 37_260 - 17_534);
+        assert_eq!(position.unlock_time, NOW + 180 * DAY);
+        // A second claim adds another full term
+        assert!(position.relock_on_claim(&staking_authority, 100, NOW + 20 * DAY).unwrap() > 0);
+        assert_eq!(position.unlock_time, NOW + 270 * DAY);
+        
+        // Claims that paid nothing leave the lock alone
+        assert_eq!(position.relock_on_claim(&staking_authority, 0, NOW + 20 * DAY).unwrap(), 0);
+        assert_eq!(position.unlock_time, NOW + 270 * DAY);
+        
+        staking_authority.max_unlock_horizon_seconds = 300 * DAY;
+        assert_error(
+            position.relock_on_claim(&staking_authority, 100, NOW + 20 * DAY),
+            StakingError::LockHorizonExceeded,
+        );
+    }
+
+    #[test]
+    fn next_event_is_the_earliest_upcoming_boundary() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        staking_authority.cooling_off_seconds = 2 * DAY;
+        let (timestamp, kind) = position.next_event(&staking_authority, NOW);
+        assert_eq!(timestamp, NOW + 2 * DAY);
+        assert!(kind == NextEventKind::CoolingOffEnd);
+        
+        let (timestamp, kind) = position.next_event(&staking_authority, NOW + 3 * DAY);
+        assert_eq!(timestamp, NOW + 90 * DAY);
+        assert!(kind == NextEventKind::Unlock);
+        
+        // Exhausting the epoch's claim cap surfaces the rollover, ties go to the first listed kind
+        staking_authority.epoch_seconds = DAY;
+        staking_authority.max_claim_per_epoch = 100;
+        position.record_epoch_claim(&staking_authority, 100, NOW + 3 * DAY);
+        let (timestamp, kind) = position.next_event(&staking_authority, NOW + 3 * DAY);
+        assert_eq!(timestamp, NOW + 4 * DAY);
+        assert!(kind == NextEventKind::ClaimCapReset);
+        staking_authority.cooling_off_seconds = 4 * DAY;
+        assert!(position.next_event(&staking_authority, NOW + 3 * DAY).1 == NextEventKind::CoolingOffEnd);
+        
+        position.unbonding = true;
+        position.cooldown_end = NOW + 100 * DAY;
+        let (timestamp, kind) = position.next_event(&staking_authority, NOW + 95 * DAY);
+        assert_eq!(timestamp, NOW + 100 * DAY);
+        assert!(kind == NextEventKind::CooldownEnd);
+        let (timestamp, kind) = position.next_event(&staking_authority, NOW + 100 * DAY);
+        assert_eq!(timestamp, 0);
+        assert!(kind == NextEventKind::None);
+        
+        position.is_active = false;
+        assert!(position.next_event(&staking_authority, NOW).1 == NextEventKind::None);
+    }
+
+    #[test]
+    fn claim_fee_is_charged_in_lamports_on_paying_claims() {
+        let mut staking_authority = staking_authority();
+        let fee_collector = Pubkey::new_unique();
+        staking_authority.claim_fee_lamports = 5_000;
+        staking_authority.fee_collector = fee_collector;
+        assert_eq!(staking_authority.claim_fee_due(1), 5_000);
+        assert_eq!(staking_authority.claim_fee_due(0), 0);
+        
+        assert!(staking_authority.check_fee_collector(Some(fee_collector)).is_ok());
+        assert_error(staking_authority.check_fee_collector(None), StakingError::InvalidFeeCollector);
+        assert_error(
+            staking_authority.check_fee_collector(Some(Pubkey::new_unique())),
+            StakingError::InvalidFeeCollector,
+        );
+    }
+
+    #[test]
+    fn zero_claim_fee_needs_no_fee_collector() {
+        let staking_authority = staking_authority();
+        assert_eq!(staking_authority.claim_fee_due(150_000), 0);
+        assert!(staking_authority.check_fee_collector(None).is_ok());
+        assert!(staking_authority.check_fee_collector(Some(Pubkey::new_unique())).is_ok());
+    }
+
+    #[test]
+    fn governance_must_be_a_key_other_than_the_admin() {
+        let mut staking_authority = staking_authority();
+        staking_authority.authority = Pubkey::new_unique();
+        assert_error(staking_authority.assign_governance(Pubkey::default()), StakingError::InvalidGovernance);
+        assert_error(staking_authority.assign_governance(staking_authority.authority), StakingError::InvalidGovernance);
+        
+        let governance = Pubkey::new_unique();
+        staking_authority.assign_governance(governance).unwrap();
+        assert_eq!(staking_authority.governance, governance);
+        
+        // A rotation is held to the same rule and leaves the current key in place when rejected
+        assert_error(staking_authority.assign_governance(staking_authority.authority), StakingError::InvalidGovernance);
+        assert_eq!(staking_authority.governance, governance);
+    }
+
+    fn slashed_position(slash_includes_rewards: bool) -> (StakeAccount, StakingAuthority) {
+        let mut staking_authority = staking_authority();
+        staking_authority.slash_includes_rewards = slash_includes_rewards;
+        let mut position = open_position(1_000_000, 365, NOW);
+        staking_authority.total_staked = 1_000_000;
+        staking_authority.add_tier_stake(position.tier_index, 1_000_000);
+        staking_authority.committed_liability = position.reward_liability;
+        
+        let clock = clock_at(NOW + SECONDS_PER_YEAR / 2);
+        assert_eq!(position.slash(&mut staking_authority, 2000, &clock).0, 200_000);
+        assert_eq!(position.deposit_amount, 800_000);
+        assert_eq!(staking_authority.total_staked, 800_000);
+        assert_eq!(staking_authority.tier_staked[3], 800_000);
+        (position, staking_authority)
+    }
+
+    #[test]
+    fn slash_leaves_settled_rewards_untouched_by_default() {
+        let (position, staking_authority) = slashed_position(false);
+        let clock = clock_at(NOW + SECONDS_PER_YEAR / 2);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), 75_000);
+        
+        // The rest of the year accrues on the remaining principal
+        let clock = clock_at(NOW + SECONDS_PER_YEAR);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), 75_000 + 60_000);
+    }
+
+    #[test]
+    fn slash_cuts_settled_rewards_pro_rata_when_configured() {
+        let (position, staking_authority) = slashed_position(true);
+        let clock = clock_at(NOW + SECONDS_PER_YEAR / 2);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), 60_000);
+    }
+
+    #[test]
+    fn claim_and_compound_splits_rewards_fifty_fifty() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 365, NOW);
+        staking_authority.total_staked = 1_000_000;
+        staking_authority.committed_liability = position.reward_liability;
+        
+        let clock = clock_at(NOW + SECONDS_PER_YEAR / 2);
+        let pending = position.settle_pending(&mut staking_authority, &clock);
+        assert_eq!(pending, 75_000);
+        let (claimed, compounded) = position.claim_split(&staking_authority, pending, u64::MAX, 5000, clock.unix_timestamp).unwrap();
+        assert_eq!((claimed, compounded), (37_500, 37_500));
+        
+        position.take_rewards(claimed, &clock);
+        position.compound_rewards(&mut staking_authority, compounded, &clock);
+        assert_eq!(position.deposit_amount, 1_000_000 + 37_500);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), 0);
+    }
+
+    #[test]
+    fn claim_and_compound_minimum_applies_to_the_clamped_payout() {
+        let mut staking_authority = staking_authority();
+        let position = open_position(1_000_000, 365, NOW);
+        staking_authority.min_claimable_rewards = 35_000;
+        assert!(position.claim_split(&staking_authority, 75_000, u64::MAX, 5000, NOW).is_ok());
+        
+        // The epoch cap trims the requested 37,500 below the minimum
+        staking_authority.max_claim_per_epoch = 30_000;
+        assert_error(
+            position.claim_split(&staking_authority, 75_000, u64::MAX, 5000, NOW),
+            StakingError::BelowMinimumClaim,
+        );
+        // A pure compound pays nothing out and needs no minimum
+        assert_eq!(position.claim_split(&staking_authority, 75_000, u64::MAX, 0, NOW).unwrap(), (0, 75_000));
+    }
+
+    #[test]
+    fn reward_trace_components_add_up_to_pending_rewards() {
+        let mut staking_authority = staking_authority();
+        staking_authority.reward_rate_per_second = 10;
+        staking_authority.index_updated_at = NOW;
+        let mut position = open_position(1_000_000, 90, NOW);
+        position.sync_reward_index(&mut staking_authority, &clock_at(NOW));
+        position.accrued_before_cap = 500;
+        position.accrued_unclaimed = 1_000;
+        
+        let trace = position.reward_trace(&staking_authority, &clock_at(NOW + 10 * DAY));
+        let window = (trace.principal_seconds + trace.deposit_amount as u128 * trace.accrual_elapsed as u128)
+            * trace.accrual_apy as u128
+            / (10000 * trace.seconds_per_year as u128);
+        let index = trace.effective_shares * trace.acc_reward_per_share / REWARD_INDEX_PRECISION - trace.reward_debt;
+        assert_eq!((window, index), (2_191, 8_640_000));
+        assert_eq!(
+            trace.accrued_before_cap + trace.accrued_unclaimed + window as u64 + index as u64,
+            trace.available_rewards,
+        );
+        assert_eq!(trace.available_rewards, position.pending_rewards(&staking_authority, &clock_at(NOW + 10 * DAY)));
+    }
+
+    #[test]
+    fn positions_ever_opened_only_counts_up() {
+        let mut staking_authority = staking_authority();
+        staking_authority.record_position_opened(1, 1_000, 20);
+        staking_authority.record_position_opened(2, 2_000, 40);
+        assert_eq!((staking_authority.staker_count, staking_authority.positions_ever_opened), (2, 2));
+        
+        staking_authority.record_position_closed(1, 1_000, 20);
+        assert_eq!((staking_authority.staker_count, staking_authority.positions_ever_opened), (1, 2));
+        assert_eq!(staking_authority.total_staked, 2_000);
+        assert_eq!(staking_authority.committed_liability, 40);
+        
+        staking_authority.record_position_opened(1, 500, 10);
+        assert_eq!((staking_authority.staker_count, staking_authority.positions_ever_opened), (2, 3));
+    }
+
+    #[test]
+    fn new_stakes_frozen_only_blocks_opening_positions() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        staking_authority.record_position_opened(position.tier_index, position.deposit_amount, 0);
+        staking_authority.new_stakes_frozen = true;
+        assert_error(staking_authority.check_new_stakes_open(), StakingError::NewStakesFrozen);
+        
+        // The existing position still accrues, claims and closes while frozen
+        let unlocked = clock_at(position.unlock_time);
+        assert!(position.settle_pending(&mut staking_authority, &unlocked) > 0);
+        staking_authority.record_position_closed(position.tier_index, position.deposit_amount, 0);
+        assert_eq!((staking_authority.staker_count, staking_authority.total_staked), (0, 0));
+        
+        staking_authority.new_stakes_frozen = false;
+        assert!(staking_authority.check_new_stakes_open().is_ok());
+    }
+
+    #[test]
+    fn beneficiary_claims_only_after_the_owner_goes_inactive() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        position.beneficiary = Some(Pubkey::new_unique());
+        assert_error(
+            position.check_owner_inactive(&staking_authority, NOW + 365 * DAY),
+            StakingError::BeneficiaryClaimsDisabled,
+        );
+        
+        staking_authority.beneficiary_inactivity_seconds = 180 * DAY;
+        assert_error(
+            position.check_owner_inactive(&staking_authority, NOW + 180 * DAY),
+            StakingError::OwnerStillActive,
+        );
+        assert!(position.check_owner_inactive(&staking_authority, NOW + 180 * DAY + 1).is_ok());
+        
+        // An owner action restarts the inactivity window
+        position.owner_last_active = NOW + 100 * DAY;
+        assert_error(
+            position.check_owner_inactive(&staking_authority, NOW + 280 * DAY),
+            StakingError::OwnerStillActive,
+        );
+        assert!(position.check_owner_inactive(&staking_authority, NOW + 280 * DAY + 1).is_ok());
+    }
+
+    #[test]
+    fn principal_borrowing_is_capped_and_repaid_before_funding() {
+        let mut staking_authority = staking_authority();
+        staking_authority.total_staked = 1_000_000;
+        assert_error(staking_authority.principal_debt_after_borrow(1), StakingError::PrincipalBorrowExceeded);
+        
+        // Up to 10% of total_staked may be outstanding
+        staking_authority.max_principal_borrow_bps = 1000;
+        staking_authority.principal_debt = staking_authority.principal_debt_after_borrow(60_000).unwrap();
+        staking_authority.principal_debt = staking_authority.principal_debt_after_borrow(40_000).unwrap();
+        assert_eq!(staking_authority.principal_debt, 100_000);
+        assert_error(staking_authority.principal_debt_after_borrow(1), StakingError::PrincipalBorrowExceeded);
+        
+        // Funding repays the debt first, only the rest reaches the rewards pool
+        assert_eq!(staking_authority.repay_principal(30_000), 30_000);
+        assert_eq!(staking_authority.principal_debt, 70_000);
+        assert_eq!(staking_authority.repay_principal(100_000), 70_000);
+        assert_eq!(staking_authority.principal_debt, 0);
+        assert_eq!(staking_authority.repay_principal(5_000), 0);
+    }
+
+    #[test]
+    fn position_liability_drops_by_what_a_claim_pays() {
+        let staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        
+        // 8% on 1_000_000 over the full 90 days
+        let liability = position.liability(&staking_authority, &clock_at(NOW));
+        assert_eq!((liability.accrued_unpaid, liability.future_accrual), (0, 19_726));
+        
+        // 30 days in: 6_575 accrued, 60 days still to accrue
+        let clock = clock_at(NOW + 30 * DAY);
+        let liability = position.liability(&staking_authority, &clock);
+        assert_eq!((liability.accrued_unpaid, liability.future_accrual), (6_575, 13_150));
+        assert_eq!(liability.total_liability, 19_725);
+        
+        position.settle_rewards(&staking_authority, &clock);
+        position.take_rewards(6_575, &clock);
+        let liability = position.liability(&staking_authority, &clock);
+        assert_eq!((liability.accrued_unpaid, liability.total_liability), (0, 13_150));
+    }
+
+    #[test]
+    fn reservations_expire_after_valid_until_to_their_creator() {
+        let reservation = reservation(800, NOW);
+        assert!(!reservation.is_expired(NOW + 30 * DAY));
+        assert!(reservation.is_expired(NOW + 30 * DAY + 1));
+        
+        // The rent goes to `owner`, whose key the reservation address is derived from
+        let (creator, other, mint) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let reservation_address = |owner: &Pubkey| {
+            Pubkey::find_program_address(&[b"stake_reservation", owner.as_ref(), mint.as_ref()], &crate::ID).0
+        };
+        assert_ne!(reservation_address(&creator), reservation_address(&other));
+    }
+
+    #[test]
+    fn exhausting_a_tier_budget_only_stops_that_tier() {
+        let mut staking_authority = staking_authority();
+        let mut ninety_day = open_position(1_000_000, 90, NOW);
+        let hundred_eighty_day = open_position(1_000_000, 180, NOW);
+        staking_authority.tier_emission_budgeted[ninety_day.tier_index as usize] = true;
+        staking_authority.tier_emission_remaining[ninety_day.tier_index as usize] = 2_000;
+        
+        // A 2_191 claim on day 10 uses up the 90-day tier's budget
+        let clock = clock_at(NOW + 10 * DAY);
+        ninety_day.settle_rewards(&staking_authority, &clock);
+        let paid = ninety_day.pending_rewards(&staking_authority, &clock);
+        assert_eq!(paid, 2_191);
+        ninety_day.take_rewards(paid, &clock);
+        staking_authority.record_tier_emission(ninety_day.tier_index, paid, &clock);
+        assert_eq!(staking_authority.tier_budget_exhausted_at[ninety_day.tier_index as usize], NOW + 10 * DAY);
+        
+        // The 90-day tier earns nothing more, the 180-day tier keeps accruing
+        let later = clock_at(NOW + 20 * DAY);
+        assert_eq!(ninety_day.pending_rewards(&staking_authority, &later), 0);
+        assert_eq!(hundred_eighty_day.pending_rewards(&staking_authority, &clock), 3_287);
+        assert_eq!(hundred_eighty_day.pending_rewards(&staking_authority, &later), 6_575);
+    }
+
+    #[test]
+    fn tier_changes_only_move_active_positions_to_longer_tiers() {
+        let mut position = open_position(1_000_000, 180, NOW);
+        assert_error(position.check_tier_change(1), StakingError::InvalidTierChange);
+        assert_error(position.check_tier_change(position.tier_index), StakingError::InvalidTierChange);
+        assert_error(position.check_tier_change(TIER_COUNT as u8), StakingError::InvalidTier);
+        position.check_tier_change(3).unwrap();
+        
+        position.unbonding = true;
+        assert_error(position.check_tier_change(3), StakingError::PositionUnbonding);
+        position.unbonding = false;
+        position.is_active = false;
+        assert_error(position.check_tier_change(3), StakingError::InactiveStake);
+    }
+
+    #[test]
+    fn unstake_to_pays_any_open_account_of_the_mint_and_only_the_owner() {
+        let mut staking_authority = staking_authority();
+        staking_authority.token_mint = Pubkey::new_unique();
+        let (owner, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        
+        // The destination need not belong to the owner
+        let destination = token_account(staking_authority.token_mint, other, None, AccountState::Initialized);
+        assert!(staking_authority.check_unstake_destination(&destination).is_ok());
+        let frozen = token_account(staking_authority.token_mint, other, None, AccountState::Frozen);
+        assert_error(staking_authority.check_unstake_destination(&frozen), StakingError::InvalidDestination);
+        let other_mint = token_account(Pubkey::new_unique(), owner, None, AccountState::Initialized);
+        assert_error(staking_authority.check_unstake_destination(&other_mint), StakingError::InvalidDestination);
+        
+        // Another signer's seeds never reach the owner's position
+        assert_ne!(
+            stake_account_address(&other, &staking_authority.token_mint),
+            stake_account_address(&owner, &staking_authority.token_mint),
+        );
+    }
+
+    #[test]
+    fn undercollateralized_only_below_the_reserve_ratio() {
+        let mut staking_authority = staking_authority();
+        staking_authority.committed_liability = 200_000;
+        staking_authority.reserve_ratio_bps = 5_000;
+        
+        // The pool must hold 50% of committed liability: 100_000
+        assert!(staking_authority.is_undercollateralized(99_999));
+        assert!(!staking_authority.is_undercollateralized(100_000));
+        assert!(!staking_authority.is_undercollateralized(100_001));
+    }
+
+    #[test]
+    fn unbonding_stops_accrual_and_holds_withdrawal_until_the_cooldown_ends() {
+        let mut staking_authority = staking_authority();
+        staking_authority.unbonding_seconds = 7 * DAY;
+        let mut position = open_position(1_000_000, 90, NOW);
+        assert_error(
+            position.begin_unbonding(&mut staking_authority, &clock_at(NOW + 89 * DAY)),
+            StakingError::StakingPeriodNotEnded,
+        );
+        assert_error(position.check_cooldown_ended(&staking_authority, NOW + 90 * DAY), StakingError::CooldownNotEnded);
+        
+        // Rewards up to the request stay owed, the cooldown adds nothing
+        position.begin_unbonding(&mut staking_authority, &clock_at(NOW + 90 * DAY)).unwrap();
+        assert_eq!(position.cooldown_end, NOW + 97 * DAY);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock_at(NOW + 90 * DAY)), 19_726);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock_at(NOW + 97 * DAY)), 19_726);
+        
+        assert_error(
+            position.check_cooldown_ended(&staking_authority, NOW + 97 * DAY - 1),
+            StakingError::CooldownNotEnded,
+        );
+        assert!(position.check_cooldown_ended(&staking_authority, NOW + 97 * DAY).is_ok());
+    }
+
+    #[test]
+    fn reward_rate_changes_only_apply_from_the_change_on() {
+        let mut staking_authority = staking_authority();
+        staking_authority.reward_rate_per_second = 10;
+        staking_authority.index_updated_at = NOW;
+        let mut position = open_position(1_000_000, 90, NOW);
+        position.sync_reward_index(&mut staking_authority, &clock_at(NOW));
+        
+        let clock = clock_at(NOW + 10 * DAY);
+        let accrued = position.pending_rewards(&staking_authority, &clock);
+        assert_eq!(staking_authority.change_reward_rate(100, clock.unix_timestamp), 10);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), accrued);
+        
+        // Ten more days at 100 per second on top of the first ten at 10
+        let later = clock_at(NOW + 20 * DAY);
+        let index_rewards =
+            position.pending_rewards(&staking_authority, &later) - position.window_rewards(&staking_authority, &later);
+        assert_eq!(index_rewards, 8_640_000 + 86_400_000);
+    }
+
+    #[test]
+    fn realized_apy_reflects_a_boost_over_the_base_tier() {
+        let staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        
+        // 30 days at the base 8%, claimed
+        let clock = clock_at(NOW + 30 * DAY);
+        position.settle_rewards(&staking_authority, &clock);
+        position.take_rewards(6_575, &clock);
+        let realized = position.realized_apy(&staking_authority, &clock);
+        assert_eq!((realized.total_rewards_earned, realized.realized_apy_bps), (6_575, 799));
+        
+        // Then 30 days boosted to 12%, unclaimed
+        position.boost_bps = 400;
+        let realized = position.realized_apy(&staking_authority, &clock_at(NOW + 60 * DAY));
+        assert_eq!((realized.total_rewards_earned, realized.elapsed_seconds), (6_575 + 9_863, 60 * DAY));
+        assert_eq!(realized.realized_apy_bps, 999);
+    }
+
+    #[test]
+    fn increases_wait_out_the_minimum_interval() {
+        let mut staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        assert!(position.check_increase_interval(&staking_authority, NOW).is_ok());
+        
+        staking_authority.min_increase_interval = DAY;
+        assert_error(
+            position.check_increase_interval(&staking_authority, NOW + DAY - 1),
+            StakingError::IncreaseTooSoon,
+        );
+        assert!(position.check_increase_interval(&staking_authority, NOW + DAY).is_ok());
+        
+        // Each increase restarts the interval
+        position.last_increased_at = NOW + 2 * DAY;
+        assert_error(
+            position.check_increase_interval(&staking_authority, NOW + 3 * DAY - 1),
+            StakingError::IncreaseTooSoon,
+        );
+        assert!(position.check_increase_interval(&staking_authority, NOW + 3 * DAY).is_ok());
+    }
+
+    #[test]
+    fn vaults_must_be_held_undelegated_by_the_canonical_authority() {
+        let mut staking_authority = staking_authority();
+        staking_authority.token_mint = Pubkey::new_unique();
+        let (address, bump) =
+            Pubkey::find_program_address(&[b"staking_authority", staking_authority.token_mint.as_ref()], &crate::ID);
+        staking_authority.bumps.staking_authority = bump;
+        
+        let vault = token_account(staking_authority.token_mint, address, None, AccountState::Initialized);
+        assert!(staking_authority.check_vault_holder(&address, &vault).is_ok());
+        
+        let delegated = token_account(
+            staking_authority.token_mint,
+            address,
+            Some(Pubkey::new_unique()),
+            AccountState::Initialized,
+        );
+        assert_error(staking_authority.check_vault_holder(&address, &delegated), StakingError::InvalidVaultAuthority);
+        assert_error(
+            staking_authority.check_vault_holder(&Pubkey::new_unique(), &vault),
+            StakingError::InvalidVaultAuthority,
+        );
+    }
+
+    #[test]
+    fn recovery_restores_a_bump_that_derives_the_address() {
+        let (staking_authority_key, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut position = open_position(1_000_000, 90, NOW);
+        position.owner = Pubkey::new_unique();
+        position.stake_authority = staking_authority_key;
+        let (address, bump) =
+            Pubkey::find_program_address(&[b"stake_account", position.owner.as_ref(), mint.as_ref()], &crate::ID);
+        position.bump = bump.wrapping_add(1);
+        
+        assert_error(position.restore_bump(&address, &Pubkey::new_unique(), &mint), StakingError::InvalidStakeAccount);
+        assert!(position.restore_bump(&address, &staking_authority_key, &mint).unwrap());
+        assert_eq!(position.bump, bump);
+        assert_error(
+            position.restore_bump(&address, &staking_authority_key, &mint),
+            StakingError::PositionNotRecoverable,
+        );
+        
+        // No bump derives an arbitrary address, leaving only the rescue unstake
+        assert!(!position.restore_bump(&Pubkey::new_unique(), &staking_authority_key, &mint).unwrap());
+        assert_eq!(position.bump, bump);
+    }
+
+    #[test]
+    fn accrual_checkpoints_fold_the_window_once_a_day() {
+        let staking_authority = staking_authority();
+        let mut position = open_position(1_000_000, 90, NOW);
+        
+        let clock = clock_at(NOW + 10 * DAY);
+        let pending = position.pending_rewards(&staking_authority, &clock);
+        assert_eq!(position.checkpoint_accrual(&staking_authority, &clock).unwrap(), pending);
+        assert_eq!(pending, 2_191);
+        assert_eq!((position.accrued_unclaimed, position.accrual_updated_at), (2_191, NOW + 10 * DAY));
+        assert_eq!(position.window_rewards(&staking_authority, &clock), 0);
+        assert_eq!(position.pending_rewards(&staking_authority, &clock), pending);
+        
+        assert_error(
+            position.checkpoint_accrual(&staking_authority, &clock_at(NOW + 11 * DAY - 1)),
+            StakingError::CheckpointTooSoon,
+        );
+        assert_eq!(position.checkpoint_accrual(&staking_authority, &clock_at(NOW + 11 * DAY)).unwrap(), 2_410);
+    }
+}